@@ -1,40 +1,237 @@
+mod batch;
+mod borrowed;
+mod buffer;
+mod builder;
+mod endian;
+mod id;
+mod payload;
+pub mod proc;
+mod registry;
+
 use std::{error::Error, fmt};
 
-use byteorder::{ByteOrder, NativeEndian};
 use netlink_packet_core::{
-    NetlinkDeserializable, NetlinkHeader, NetlinkPayload,
-    NetlinkSerializable,
+    NetlinkDeserializable, NetlinkHeader, NetlinkMessage, NetlinkPayload,
+    NetlinkSerializable, NLMSG_DONE, NLMSG_ERROR, NLMSG_NOOP, NLMSG_OVERRUN,
 };
 
-/// Identity of a connecting process.
-#[derive(Debug, Clone, Eq, PartialEq)]
-pub struct ConnectorId {
-    idx: u32,
-    value: u32
+pub use self::{
+    batch::{ConnectorMessageBatch, ConnectorMessageRefs, ConnectorMessages},
+    borrowed::ConnectorMessageRef,
+    buffer::{ConnectorMessageBuffer, CONNECTOR_HEADER_LEN},
+    builder::{ConnectorMessageBuilder, SequenceGenerator},
+    endian::Endianness,
+    id::*,
+    payload::ConnectorPayload,
+    registry::{DecodedMessage, DecoderRegistry},
+};
+
+/// Alignment of netlink messages, `NLMSG_ALIGNTO` in the kernel.
+pub const NLMSG_ALIGNTO: usize = 4;
+
+// Largest data area a `cn_msg` can describe: the 16 bit `len` field
+// plus the most padding any parse mode accepts.
+const MAX_DATA_AREA: usize =
+    nlmsg_align(u16::MAX as usize) + NLMSG_ALIGNTO;
+
+/// Round `len` up to the netlink alignment, like `NLMSG_ALIGN`.
+pub const fn nlmsg_align(len: usize) -> usize {
+    (len + NLMSG_ALIGNTO - 1) & !(NLMSG_ALIGNTO - 1)
+}
+
+/// How strictly the bytes trailing the data of a message are checked.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub enum ParseMode {
+    /// Accept at most the padding needed to reach `NLMSG_ALIGN(len)`,
+    /// and only if it is all zeros. This is what the kernel emits.
+    #[default]
+    Strict,
+    /// Accept padding of any content, and up to [`NLMSG_ALIGNTO`] bytes
+    /// past the aligned length, as found in captures from some older
+    /// kernels.
+    Lenient,
+}
+
+impl ParseMode {
+    // Check the bytes following `len` bytes of data. `offset` is the
+    // position of `trailer` in the payload, used for error reporting.
+    fn check_padding(
+        self,
+        len: u16,
+        trailer: &[u8],
+        offset: usize,
+    ) -> Result<(), DeserializeError> {
+        let mut max = nlmsg_align(len as usize) - len as usize;
+        if self == ParseMode::Lenient {
+            max += NLMSG_ALIGNTO;
+        }
+        let padding = trailer.len();
+        if padding > max {
+            return Err(DeserializeError::ExcessPadding { len, padding });
+        }
+        if self == ParseMode::Strict {
+            if let Some(i) = trailer.iter().position(|byte| *byte != 0) {
+                return Err(DeserializeError::NonZeroPadding {
+                    offset: offset + i,
+                    value: trailer[i],
+                });
+            }
+        }
+        Ok(())
+    }
 }
 
+/// Largest netlink payload, header included, the kernel accepts on a
+/// connector socket.
+pub const CONNECTOR_MAX_MSG_SIZE: usize = 16384;
+
 /// The netlink connector protocol relies only on one message type.
+///
+/// The data is carried as a [`ConnectorPayload`], raw bytes unless a
+/// typed payload is chosen.
 #[derive(Debug, Clone, Eq, PartialEq)]
-pub struct ConnectorMessage {
+pub struct ConnectorMessage<P = Vec<u8>> {
+    message_type: u16,
     id: ConnectorId,
     seq: u32,
     ack: u32,
     flags: u16,
-    data: Vec<u8>,
+    payload: P,
+    // The byte order the message was parsed with, and its data is in.
+    endianness: Endianness,
 }
 
 impl ConnectorMessage {
 
     pub fn new(idx: u32, value: u32, seq: u32, ack: u32, flags: u16, data: Vec<u8>) -> Self {
         ConnectorMessage {
-            id: ConnectorId { idx, value },
+            message_type: NLMSG_DONE,
+            id: ConnectorId::new(idx, value),
             seq,
             ack,
             flags,
-            data,
+            payload: data,
+            endianness: Endianness::Native,
         }
     }
 
+    /// Start building a message with named fields.
+    pub fn builder() -> ConnectorMessageBuilder {
+        ConnectorMessageBuilder::new()
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.payload
+    }
+
+    /// Borrow this message as a [`ConnectorMessageRef`].
+    pub fn as_borrowed(&self) -> ConnectorMessageRef<'_> {
+        ConnectorMessageRef::new(
+            self.id.idx,
+            self.id.value,
+            self.seq,
+            self.ack,
+            self.flags,
+            &self.payload,
+        )
+        .with_message_type(self.message_type)
+        .with_endianness(self.endianness)
+    }
+
+    /// Decode the data as a typed payload, checking that the message is
+    /// addressed to the payload's connector.
+    ///
+    /// The data is read in the byte order the header was parsed with,
+    /// see [`Self::endianness`].
+    pub fn decode<P: ConnectorPayload>(
+        &self,
+    ) -> Result<ConnectorMessage<P>, DeserializeError> {
+        self.as_borrowed().decode()
+    }
+
+    /// Like [`Self::decode`], for data in the given byte order.
+    pub fn decode_with_endianness<P: ConnectorPayload>(
+        &self,
+        endianness: Endianness,
+    ) -> Result<ConnectorMessage<P>, DeserializeError> {
+        self.as_borrowed().decode_with_endianness(endianness)
+    }
+
+    /// Parse a `struct cn_msg` and the data following it, checking the
+    /// padding in [`ParseMode::Strict`] mode.
+    ///
+    /// Every access is bounds checked, so malformed input yields a
+    /// [`DeserializeError`] rather than a panic.
+    pub fn parse(payload: &[u8]) -> Result<Self, DeserializeError> {
+        Self::parse_with_mode(payload, ParseMode::Strict)
+    }
+
+    /// Parse a `struct cn_msg`, checking the padding according to `mode`.
+    pub fn parse_with_mode(
+        payload: &[u8],
+        mode: ParseMode,
+    ) -> Result<Self, DeserializeError> {
+        ConnectorMessageRef::parse_with_mode(payload, mode)
+            .map(|message| message.to_owned())
+    }
+
+    /// Parse the message in `buf`, reading its fields in the buffer's
+    /// byte order and checking the padding according to `mode`.
+    pub fn parse_buffer(
+        buf: ConnectorMessageBuffer<&[u8]>,
+        mode: ParseMode,
+    ) -> Result<Self, DeserializeError> {
+        ConnectorMessageRef::parse_buffer(buf, mode)
+            .map(|message| message.to_owned())
+    }
+}
+
+impl<P: ConnectorPayload> ConnectorMessage<P> {
+    /// Create a message carrying `payload`, addressed to the payload's
+    /// [`ConnectorPayload::ID`] if it has one.
+    pub fn from_payload(payload: P) -> Self {
+        ConnectorMessage {
+            message_type: NLMSG_DONE,
+            id: P::ID.unwrap_or(ConnectorId::new(0, 0)),
+            seq: 0,
+            ack: 0,
+            flags: 0,
+            payload,
+            endianness: Endianness::Native,
+        }
+    }
+
+    /// Replace the netlink message type, [`NLMSG_DONE`] by default as
+    /// used by the kernel.
+    pub fn with_message_type(mut self, message_type: u16) -> Self {
+        self.message_type = message_type;
+        self
+    }
+
+    pub fn with_id(mut self, id: ConnectorId) -> Self {
+        self.id = id;
+        self
+    }
+
+    pub fn with_seq(mut self, seq: u32) -> Self {
+        self.seq = seq;
+        self
+    }
+
+    pub fn with_ack(mut self, ack: u32) -> Self {
+        self.ack = ack;
+        self
+    }
+
+    pub fn with_flags(mut self, flags: u16) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    pub fn id(&self) -> ConnectorId {
+        self.id
+    }
+
     pub fn idx(&self) -> u32 {
         self.id.idx
     }
@@ -55,8 +252,94 @@ impl ConnectorMessage {
         self.flags
     }
 
-    pub fn data(&self) -> &[u8] {
-        &self.data
+    pub fn payload(&self) -> &P {
+        &self.payload
+    }
+
+    pub fn into_payload(self) -> P {
+        self.payload
+    }
+
+    /// The byte order the message was parsed with, the host byte order
+    /// for messages built in this process.
+    pub fn endianness(&self) -> Endianness {
+        self.endianness
+    }
+
+    /// Serialize the payload in the message's byte order, see
+    /// [`Self::endianness`], turning this into an untyped message.
+    pub fn to_raw(&self) -> ConnectorMessage {
+        let mut data = vec![0; self.payload.payload_len()];
+        self.payload.emit_payload(&mut data, self.endianness);
+        ConnectorMessage {
+            message_type: self.message_type,
+            id: self.id,
+            seq: self.seq,
+            ack: self.ack,
+            flags: self.flags,
+            payload: data,
+            endianness: self.endianness,
+        }
+    }
+
+    /// Check that this message can be serialized: the payload must fit
+    /// the `len` field and the message must not exceed
+    /// [`CONNECTOR_MAX_MSG_SIZE`].
+    pub fn validate(&self) -> Result<(), SerializeError> {
+        check_record(self.payload.payload_len())
+    }
+
+    /// Serialize this message into `buffer`, which must be exactly
+    /// [`NetlinkSerializable::buffer_len`] bytes long, in the byte order
+    /// of [`Self::endianness`] so that the header matches the data.
+    pub fn try_emit(&self, buffer: &mut [u8]) -> Result<(), SerializeError> {
+        self.try_emit_with_endianness(buffer, self.endianness)
+    }
+
+    /// Like [`Self::try_emit`], writing the fields in the given byte
+    /// order.
+    pub fn try_emit_with_endianness(
+        &self,
+        buffer: &mut [u8],
+        endianness: Endianness,
+    ) -> Result<(), SerializeError> {
+        self.validate()?;
+        emit_padded(buffer, self.buffer_len(), |buffer| {
+            self.emit_record(buffer, endianness)
+        })
+    }
+
+    // Write the header and payload without padding, returning the
+    // number of bytes written. The message must have been validated.
+    fn emit_record(&self, buffer: &mut [u8], endianness: Endianness) -> usize {
+        let len = self.payload.payload_len();
+        let header = (self.id, self.seq, self.ack, self.flags);
+        let mut buf = emit_header(buffer, endianness, header, len);
+        self.payload.emit_payload(buf.data_mut(), endianness);
+        CONNECTOR_HEADER_LEN + len
+    }
+
+    /// Extract the connector message from a deserialized netlink message.
+    ///
+    /// `netlink-packet-core` decodes `NLMSG_DONE` frames itself, so the
+    /// kernel's connector messages arrive as [`NetlinkPayload::Done`]
+    /// rather than [`NetlinkPayload::InnerMessage`]. This reassembles
+    /// them, and rejects error, noop and overrun frames.
+    pub fn from_netlink(
+        message: NetlinkMessage<ConnectorMessage<P>>,
+    ) -> Result<Self, DeserializeError> {
+        let (header, payload) = message.into_parts();
+        match payload {
+            NetlinkPayload::InnerMessage(message) => Ok(message),
+            NetlinkPayload::Done(done) => {
+                let mut payload = done.code.to_ne_bytes().to_vec();
+                payload.extend_from_slice(&done.extended_ack);
+                ConnectorMessage::deserialize(&header, &payload)
+            }
+            _ => Err(DeserializeError::UnexpectedMessageType {
+                message_type: header.message_type,
+            }),
+        }
     }
 }
 
@@ -64,72 +347,223 @@ impl ConnectorMessage {
 // required because `NetlinkDeserializable::Error` must implement
 // `std::error::Error`, so a simple `String` won't cut it.
 #[derive(Debug, Clone, Eq, PartialEq)]
-pub struct DeserializeError(&'static str);
+#[non_exhaustive]
+pub enum DeserializeError {
+    /// The payload is too short to hold the structure being parsed.
+    Truncated { needed: usize, got: usize },
+    /// The `len` field announces more data than the payload carries.
+    LengthExceedsPayload { len: u16, available: usize },
+    /// More bytes follow the data than padding can account for.
+    ExcessPadding { len: u16, padding: usize },
+    /// A padding byte is not zero.
+    NonZeroPadding { offset: usize, value: u8 },
+    /// The data area is larger than a `cn_msg` can describe.
+    PayloadTooLarge { size: usize, max: usize },
+    /// The netlink message is a control message, not a connector message.
+    UnexpectedMessageType { message_type: u16 },
+    /// A typed payload was parsed from a message for another connector.
+    IdMismatch {
+        expected: ConnectorId,
+        got: ConnectorId,
+    },
+    /// The data length matches none of the layouts of a typed payload.
+    InvalidLength { len: usize },
+}
 
-impl Error for DeserializeError {
-    fn description(&self) -> &str {
-        self.0
-    }
-    fn source(&self) -> Option<&(dyn Error + 'static)> {
-        None
+impl Error for DeserializeError {}
+
+impl fmt::Display for DeserializeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeserializeError::Truncated { needed, got } => write!(
+                f,
+                "truncated payload: needed {needed} bytes, got {got}"
+            ),
+            DeserializeError::LengthExceedsPayload { len, available } => {
+                write!(
+                    f,
+                    "data length {len} exceeds the {available} bytes \
+                     available"
+                )
+            }
+            DeserializeError::ExcessPadding { len, padding } => write!(
+                f,
+                "{padding} trailing bytes after {len} bytes of data \
+                 exceed the allowed padding"
+            ),
+            DeserializeError::NonZeroPadding { offset, value } => write!(
+                f,
+                "padding byte at offset {offset} is {value:#04x}, not zero"
+            ),
+            DeserializeError::PayloadTooLarge { size, max } => write!(
+                f,
+                "data area of {size} bytes exceeds the maximum of {max}"
+            ),
+            DeserializeError::UnexpectedMessageType { message_type } => {
+                write!(
+                    f,
+                    "netlink message type {message_type} does not carry a \
+                     connector message"
+                )
+            }
+            DeserializeError::IdMismatch { expected, got } => write!(
+                f,
+                "message for connector {got} where {expected} was expected"
+            ),
+            DeserializeError::InvalidLength { len } => write!(
+                f,
+                "data length {len} matches no layout of the payload"
+            ),
+        }
     }
 }
 
-impl fmt::Display for DeserializeError {
+/// Error returned when a message cannot be serialized.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum SerializeError {
+    /// The data does not fit the 16 bit `len` field.
+    LengthOverflow { len: usize },
+    /// The serialized message exceeds [`CONNECTOR_MAX_MSG_SIZE`].
+    MessageTooLarge { size: usize, max: usize },
+    /// The output buffer does not match the serialized length.
+    BufferSize { expected: usize, got: usize },
+    /// The netlink header does not match its payload, the message was
+    /// not finalized.
+    NotFinalized,
+}
+
+impl Error for SerializeError {}
+
+impl fmt::Display for SerializeError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.0)
+        match self {
+            SerializeError::LengthOverflow { len } => write!(
+                f,
+                "data length {len} does not fit the 16 bit length field"
+            ),
+            SerializeError::MessageTooLarge { size, max } => write!(
+                f,
+                "message of {size} bytes exceeds the kernel limit of {max}"
+            ),
+            SerializeError::BufferSize { expected, got } => write!(
+                f,
+                "buffer of {got} bytes given, message needs {expected}"
+            ),
+            SerializeError::NotFinalized => {
+                write!(f, "netlink header does not match the message")
+            }
+        }
+    }
+}
+
+// Check that a message with `len` bytes of data can be serialized, see
+// `ConnectorMessage::validate`.
+fn check_record(len: usize) -> Result<(), SerializeError> {
+    if len > u16::MAX as usize {
+        return Err(SerializeError::LengthOverflow { len });
+    }
+    check_message_size(CONNECTOR_HEADER_LEN + nlmsg_align(len))
+}
+
+// Check that a serialized message of `size` bytes is within the kernel
+// limit.
+fn check_message_size(size: usize) -> Result<(), SerializeError> {
+    if size > CONNECTOR_MAX_MSG_SIZE {
+        return Err(SerializeError::MessageTooLarge {
+            size,
+            max: CONNECTOR_MAX_MSG_SIZE,
+        });
+    }
+    Ok(())
+}
+
+// The `id`, `seq`, `ack` and `flags` fields of a `cn_msg` header.
+type Header = (ConnectorId, u32, u32, u16);
+
+// Write a `cn_msg` header announcing `len` bytes of data, returning the
+// record so that the data can be written into it. `len` must have
+// passed `check_record`.
+fn emit_header(
+    buffer: &mut [u8],
+    endianness: Endianness,
+    (id, seq, ack, flags): Header,
+    len: usize,
+) -> ConnectorMessageBuffer<&mut [u8]> {
+    let mut buf = ConnectorMessageBuffer::with_endianness(
+        &mut buffer[..CONNECTOR_HEADER_LEN + len],
+        endianness,
+    );
+    buf.set_idx(id.idx);
+    buf.set_value(id.value);
+    buf.set_seq(seq);
+    buf.set_ack(ack);
+    buf.set_len(len as u16);
+    buf.set_flags(flags);
+    buf
+}
+
+// Fill `buffer`, which must be exactly `expected` bytes long, with the
+// records written by `emit`, which returns their length, and zero the
+// padding after them, whatever the buffer held before.
+fn emit_padded(
+    buffer: &mut [u8],
+    expected: usize,
+    emit: impl FnOnce(&mut [u8]) -> usize,
+) -> Result<(), SerializeError> {
+    if buffer.len() != expected {
+        return Err(SerializeError::BufferSize {
+            expected,
+            got: buffer.len(),
+        });
+    }
+    let end = emit(buffer);
+    buffer[end..].fill(0);
+    Ok(())
+}
+
+// Reject the netlink control messages, which never carry a `cn_msg`.
+fn check_message_type(message_type: u16) -> Result<u16, DeserializeError> {
+    match message_type {
+        NLMSG_NOOP | NLMSG_ERROR | NLMSG_OVERRUN => {
+            Err(DeserializeError::UnexpectedMessageType { message_type })
+        }
+        _ => Ok(message_type),
     }
 }
 
 // NetlinkDeserializable implementation
-impl NetlinkDeserializable for ConnectorMessage {
+impl<P: ConnectorPayload> NetlinkDeserializable for ConnectorMessage<P> {
     type Error = DeserializeError;
 
     fn deserialize(
-        _header: &NetlinkHeader,
+        header: &NetlinkHeader,
         payload: &[u8],
     ) -> Result<Self, Self::Error> {
-        let idx: u32 = NativeEndian::read_u32(&payload[0..4]);
-        let value: u32 = NativeEndian::read_u32(&payload[4..8]);
-        let seq: u32 = NativeEndian::read_u32(&payload[8..12]);
-        let ack: u32 = NativeEndian::read_u32(&payload[12..16]);
-        let len: u16 = NativeEndian::read_u16(&payload[16..18]);
-        let flags: u16 = NativeEndian::read_u16(&payload[18..20]);
-        let data = &payload[20..];
-
-        // the data space is padded to 4 byte blocks.
-        if (data.len() as i16 - len as i16) < 0 || (data.len() as u16 - len) > 4 {
-            return Err(DeserializeError("Invalid data length"));
-        }
-
-        Ok(ConnectorMessage {
-            id: ConnectorId { idx, value },
-            seq,
-            ack,
-            flags,
-            data: data[0..len as usize].to_vec(),
-        })
+        let message_type = check_message_type(header.message_type)?;
+        Ok(ConnectorMessageRef::parse(payload)?
+            .decode::<P>()?
+            .with_message_type(message_type))
     }
 }
 
 // NetlinkSerializable implementation
-impl NetlinkSerializable for ConnectorMessage {
+impl<P: ConnectorPayload> NetlinkSerializable for ConnectorMessage<P> {
     fn message_type(&self) -> u16 {
-        0
+        self.message_type
     }
 
+    // The data is padded so that the next message starts aligned.
     fn buffer_len(&self) -> usize {
-        20 + self.data.len()
+        CONNECTOR_HEADER_LEN + nlmsg_align(self.payload.payload_len())
     }
 
+    // The trait offers no way to report errors, so messages that fail
+    // `validate` panic here instead of being silently truncated.
     fn serialize(&self, buffer: &mut [u8]) {
-        NativeEndian::write_u32(&mut buffer[0..4], self.id.idx);
-        NativeEndian::write_u32(&mut buffer[4..8], self.id.value);
-        NativeEndian::write_u32(&mut buffer[8..12], self.seq);
-        NativeEndian::write_u32(&mut buffer[12..16], self.ack);
-        NativeEndian::write_u16(&mut buffer[16..18], self.data.len() as u16);
-        NativeEndian::write_u16(&mut buffer[18..20], self.flags);
-        buffer[20..].copy_from_slice(&self.data);
+        if let Err(e) = self.try_emit(buffer) {
+            panic!("cannot serialize connector message: {e}");
+        }
     }
 }
 
@@ -137,8 +571,279 @@ impl NetlinkSerializable for ConnectorMessage {
 // from a ConnectorMessage. Since NetlinkMessage<T> already implements
 // From<NetlinkPayload<T>>, we just need to implement
 // From<NetlinkPayload<ConnectorMessage>> for this to work.
-impl From<ConnectorMessage> for NetlinkPayload<ConnectorMessage> {
-    fn from(message: ConnectorMessage) -> Self {
+impl<P> From<ConnectorMessage<P>> for NetlinkPayload<ConnectorMessage<P>> {
+    fn from(message: ConnectorMessage<P>) -> Self {
         NetlinkPayload::InnerMessage(message)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroI32;
+
+    use netlink_packet_core::ErrorMessage;
+
+    use super::*;
+
+    // A `cn_msg` header for connector 1:1 announcing `len` bytes of
+    // data, followed by `area` bytes of zeros.
+    fn message_bytes(len: u16, area: usize) -> Vec<u8> {
+        let mut bytes = vec![0; CONNECTOR_HEADER_LEN + area];
+        let mut buf = ConnectorMessageBuffer::new(&mut bytes[..]);
+        buf.set_idx(1);
+        buf.set_value(1);
+        buf.set_len(len);
+        bytes
+    }
+
+    #[test]
+    fn parse_truncated_header() {
+        let bytes = message_bytes(0, 0);
+        for len in 0..CONNECTOR_HEADER_LEN {
+            assert_eq!(
+                ConnectorMessage::parse(&bytes[..len]),
+                Err(DeserializeError::Truncated {
+                    needed: CONNECTOR_HEADER_LEN,
+                    got: len,
+                }),
+            );
+        }
+    }
+
+    #[test]
+    fn parse_length_exceeds_payload() {
+        let bytes = message_bytes(8, 4);
+        assert_eq!(
+            ConnectorMessage::parse(&bytes),
+            Err(DeserializeError::LengthExceedsPayload {
+                len: 8,
+                available: 4,
+            }),
+        );
+    }
+
+    #[test]
+    fn parse_payload_too_large() {
+        let bytes = message_bytes(0, MAX_DATA_AREA + 1);
+        assert_eq!(
+            ConnectorMessage::parse_with_mode(&bytes, ParseMode::Lenient),
+            Err(DeserializeError::PayloadTooLarge {
+                size: MAX_DATA_AREA + 1,
+                max: MAX_DATA_AREA,
+            }),
+        );
+    }
+
+    // Lengths above `i16::MAX` used to wrap negative and panic when
+    // slicing the data.
+    #[test]
+    fn parse_length_above_i16_max() {
+        let bytes = message_bytes(40000, 100);
+        assert_eq!(
+            ConnectorMessage::parse(&bytes),
+            Err(DeserializeError::LengthExceedsPayload {
+                len: 40000,
+                available: 100,
+            }),
+        );
+
+        let bytes = message_bytes(40000, 40000);
+        let message = ConnectorMessage::parse(&bytes).unwrap();
+        assert_eq!(message.data().len(), 40000);
+    }
+
+    #[test]
+    fn emit_parse_round_trip() {
+        for len in 0..=8 {
+            let data: Vec<u8> = (1..=len as u8).collect();
+            let message = ConnectorMessage::new(1, 1, 7, 8, 0, data);
+            let mut bytes = vec![0xff; message.buffer_len()];
+            message.try_emit(&mut bytes).unwrap();
+
+            assert_eq!(bytes.len(), CONNECTOR_HEADER_LEN + nlmsg_align(len));
+            let padding = &bytes[CONNECTOR_HEADER_LEN + len..];
+            assert!(padding.iter().all(|byte| *byte == 0));
+            for mode in [ParseMode::Strict, ParseMode::Lenient] {
+                assert_eq!(
+                    ConnectorMessage::parse_with_mode(&bytes, mode),
+                    Ok(message.clone()),
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn strict_rejects_non_zero_padding() {
+        let mut bytes = message_bytes(1, 4);
+        bytes[CONNECTOR_HEADER_LEN + 2] = 0xaa;
+        assert_eq!(
+            ConnectorMessage::parse(&bytes),
+            Err(DeserializeError::NonZeroPadding {
+                offset: CONNECTOR_HEADER_LEN + 2,
+                value: 0xaa,
+            }),
+        );
+        assert!(
+            ConnectorMessage::parse_with_mode(&bytes, ParseMode::Lenient)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn excess_padding() {
+        let bytes = message_bytes(4, 8);
+        assert_eq!(
+            ConnectorMessage::parse(&bytes),
+            Err(DeserializeError::ExcessPadding { len: 4, padding: 4 }),
+        );
+        let message =
+            ConnectorMessage::parse_with_mode(&bytes, ParseMode::Lenient)
+                .unwrap();
+        assert_eq!(message.data(), [0; 4]);
+
+        let bytes = message_bytes(4, 12);
+        for mode in [ParseMode::Strict, ParseMode::Lenient] {
+            assert_eq!(
+                ConnectorMessage::parse_with_mode(&bytes, mode),
+                Err(DeserializeError::ExcessPadding { len: 4, padding: 8 }),
+            );
+        }
+    }
+
+    #[test]
+    fn deserialize_rejects_control_messages() {
+        let mut bytes = vec![0; 24];
+        ConnectorMessage::new(1, 1, 0, 0, 0, vec![0; 4])
+            .try_emit(&mut bytes)
+            .unwrap();
+        for message_type in [NLMSG_NOOP, NLMSG_ERROR, NLMSG_OVERRUN] {
+            let mut header = NetlinkHeader::default();
+            header.message_type = message_type;
+            assert_eq!(
+                ConnectorMessage::<Vec<u8>>::deserialize(&header, &bytes),
+                Err(DeserializeError::UnexpectedMessageType { message_type }),
+            );
+        }
+    }
+
+    #[test]
+    fn message_type_round_trip() {
+        let message = ConnectorMessage::new(1, 1, 7, 8, 0, vec![1, 2, 3, 4, 5])
+            .with_message_type(0x20);
+        let mut netlink = NetlinkMessage::from(message.clone());
+        netlink.finalize();
+        assert_eq!(netlink.header.message_type, 0x20);
+        let mut bytes = vec![0; netlink.buffer_len()];
+        netlink.serialize(&mut bytes);
+
+        let netlink =
+            NetlinkMessage::<ConnectorMessage>::deserialize(&bytes).unwrap();
+        assert_eq!(netlink.header.message_type, 0x20);
+        let parsed = ConnectorMessage::from_netlink(netlink).unwrap();
+        assert_eq!(NetlinkSerializable::message_type(&parsed), 0x20);
+        assert_eq!(parsed, message);
+    }
+
+    #[test]
+    fn from_netlink_rejects_errors() {
+        let mut error = ErrorMessage::default();
+        error.code = NonZeroI32::new(-libc::EPERM);
+        let mut netlink = NetlinkMessage::<ConnectorMessage>::new(
+            NetlinkHeader::default(),
+            NetlinkPayload::Error(error),
+        );
+        netlink.finalize();
+        assert_eq!(
+            ConnectorMessage::from_netlink(netlink),
+            Err(DeserializeError::UnexpectedMessageType {
+                message_type: NLMSG_ERROR,
+            }),
+        );
+    }
+
+    #[test]
+    fn validate_length_overflow() {
+        let message = ConnectorMessage::new(1, 1, 0, 0, 0, vec![0; 65536]);
+        let error = SerializeError::LengthOverflow { len: 65536 };
+        assert_eq!(message.validate(), Err(error.clone()));
+        let mut bytes = vec![0; message.buffer_len()];
+        assert_eq!(message.try_emit(&mut bytes), Err(error));
+    }
+
+    #[test]
+    fn validate_message_too_large() {
+        let len = CONNECTOR_MAX_MSG_SIZE - CONNECTOR_HEADER_LEN;
+        let message = ConnectorMessage::new(1, 1, 0, 0, 0, vec![0; len]);
+        assert_eq!(message.validate(), Ok(()));
+
+        let message = ConnectorMessage::new(1, 1, 0, 0, 0, vec![0; len + 1]);
+        let error = SerializeError::MessageTooLarge {
+            size: CONNECTOR_MAX_MSG_SIZE + NLMSG_ALIGNTO,
+            max: CONNECTOR_MAX_MSG_SIZE,
+        };
+        assert_eq!(message.validate(), Err(error.clone()));
+        let mut bytes = vec![0; message.buffer_len()];
+        assert_eq!(message.try_emit(&mut bytes), Err(error));
+    }
+
+    #[test]
+    fn try_emit_buffer_size() {
+        let message = ConnectorMessage::new(1, 1, 0, 0, 0, vec![1, 2, 3]);
+        for len in [0, 23, 25] {
+            let mut bytes = vec![0xff; len];
+            assert_eq!(
+                message.try_emit(&mut bytes),
+                Err(SerializeError::BufferSize {
+                    expected: 24,
+                    got: len,
+                }),
+            );
+            assert!(bytes.iter().all(|byte| *byte == 0xff));
+        }
+    }
+
+    #[test]
+    fn foreign_byte_order_round_trip() {
+        for endianness in [Endianness::Big, Endianness::Little] {
+            let message = ConnectorMessage::new(
+                3,
+                1,
+                0x0102,
+                0x0103,
+                0x10,
+                vec![1, 2, 3],
+            );
+            let mut bytes = vec![0; message.buffer_len()];
+            message
+                .try_emit_with_endianness(&mut bytes, endianness)
+                .unwrap();
+            let buf =
+                ConnectorMessageBuffer::with_endianness(&bytes[..], endianness);
+            let borrowed = ConnectorMessageRef::parse_buffer(
+                buf.clone(),
+                ParseMode::Strict,
+            )
+            .unwrap();
+            assert_eq!(borrowed.endianness(), endianness);
+            assert_eq!(borrowed.seq(), 0x0102);
+            assert_eq!(borrowed.ack(), 0x0103);
+            assert_eq!(borrowed.data(), [1, 2, 3]);
+
+            let parsed =
+                ConnectorMessage::parse_buffer(buf, ParseMode::Strict).unwrap();
+            assert_eq!(parsed.endianness(), endianness);
+            assert_eq!(parsed.as_borrowed(), borrowed);
+
+            // re-emitted in the byte order it was parsed with.
+            let mut emitted = vec![0xff; parsed.buffer_len()];
+            parsed.try_emit(&mut emitted).unwrap();
+            assert_eq!(emitted, bytes);
+            let mut emitted = vec![0xff; borrowed.buffer_len()];
+            borrowed.try_emit(&mut emitted).unwrap();
+            assert_eq!(emitted, bytes);
+            let mut emitted = vec![0xff; parsed.buffer_len()];
+            parsed.serialize(&mut emitted);
+            assert_eq!(emitted, bytes);
+        }
+    }
+}
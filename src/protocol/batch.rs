@@ -0,0 +1,384 @@
+use netlink_packet_core::{
+    NetlinkDeserializable, NetlinkHeader, NetlinkMessage, NetlinkPayload,
+    NetlinkSerializable, NLMSG_DONE,
+};
+
+use super::{
+    check_message_size, check_message_type, emit_padded, nlmsg_align,
+    ConnectorMessage, ConnectorMessageBuffer, ConnectorMessageRef,
+    DeserializeError, Endianness, ParseMode, SerializeError,
+    CONNECTOR_HEADER_LEN, NLMSG_ALIGNTO,
+};
+
+/// Iterator over the `cn_msg` records packed back to back in one netlink
+/// payload, as sent by the kernel's `cn_netlink_send_mult`, borrowing
+/// each record's data.
+///
+/// Each item reports the outcome for one record. A malformed record
+/// ends the iteration, since the position of the next one is unknown.
+#[derive(Debug, Clone)]
+pub struct ConnectorMessageRefs<'a> {
+    payload: &'a [u8],
+    endianness: Endianness,
+}
+
+impl<'a> ConnectorMessageRefs<'a> {
+    pub fn new(payload: &'a [u8]) -> Self {
+        Self::with_endianness(payload, Endianness::Native)
+    }
+
+    /// Like [`Self::new`], for a payload in the given byte order.
+    pub fn with_endianness(payload: &'a [u8], endianness: Endianness) -> Self {
+        ConnectorMessageRefs {
+            payload,
+            endianness,
+        }
+    }
+
+    /// The bytes not consumed yet.
+    pub fn remaining(&self) -> &'a [u8] {
+        self.payload
+    }
+}
+
+impl<'a> Iterator for ConnectorMessageRefs<'a> {
+    type Item = Result<ConnectorMessageRef<'a>, DeserializeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = self.payload;
+        // the last record may be followed by alignment padding.
+        if rest.len() < NLMSG_ALIGNTO && rest.iter().all(|byte| *byte == 0) {
+            self.payload = &[];
+            return None;
+        }
+
+        let record = ConnectorMessageBuffer::new_checked_with_endianness(
+            rest,
+            self.endianness,
+        )
+        .map(|buf| CONNECTOR_HEADER_LEN + buf.len() as usize)
+        .and_then(|end| {
+            let buf = ConnectorMessageBuffer::with_endianness(
+                &rest[..end],
+                self.endianness,
+            );
+            let message =
+                ConnectorMessageRef::parse_buffer(buf, ParseMode::Strict)?;
+            Ok((message, end))
+        });
+
+        match record {
+            Ok((message, end)) => {
+                self.payload = &rest[end..];
+                Some(Ok(message))
+            }
+            Err(e) => {
+                self.payload = &[];
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// Like [`ConnectorMessageRefs`], copying each record into an owned
+/// [`ConnectorMessage`].
+#[derive(Debug, Clone)]
+pub struct ConnectorMessages<'a> {
+    inner: ConnectorMessageRefs<'a>,
+}
+
+impl<'a> ConnectorMessages<'a> {
+    pub fn new(payload: &'a [u8]) -> Self {
+        Self::with_endianness(payload, Endianness::Native)
+    }
+
+    /// Like [`Self::new`], for a payload in the given byte order.
+    pub fn with_endianness(payload: &'a [u8], endianness: Endianness) -> Self {
+        ConnectorMessages {
+            inner: ConnectorMessageRefs::with_endianness(payload, endianness),
+        }
+    }
+}
+
+impl Iterator for ConnectorMessages<'_> {
+    type Item = Result<ConnectorMessage, DeserializeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner
+            .next()
+            .map(|record| record.map(|message| message.to_owned()))
+    }
+}
+
+/// Several connector messages sent in a single netlink message, packed
+/// back to back like the kernel's `cn_netlink_send_mult` does.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ConnectorMessageBatch {
+    message_type: u16,
+    messages: Vec<ConnectorMessage>,
+}
+
+impl Default for ConnectorMessageBatch {
+    fn default() -> Self {
+        ConnectorMessageBatch {
+            message_type: NLMSG_DONE,
+            messages: Vec::new(),
+        }
+    }
+}
+
+impl ConnectorMessageBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the netlink message type of the batch, see
+    /// [`ConnectorMessage::with_message_type`].
+    pub fn with_message_type(mut self, message_type: u16) -> Self {
+        self.message_type = message_type;
+        self
+    }
+
+    pub fn push(&mut self, message: ConnectorMessage) {
+        self.messages.push(message);
+    }
+
+    pub fn messages(&self) -> &[ConnectorMessage] {
+        &self.messages
+    }
+
+    pub fn into_messages(self) -> Vec<ConnectorMessage> {
+        self.messages
+    }
+
+    /// Parse every record in `payload`, failing on the first malformed
+    /// one.
+    pub fn parse(payload: &[u8]) -> Result<Self, DeserializeError> {
+        ConnectorMessages::new(payload).collect()
+    }
+
+    /// Extract the batch from a deserialized netlink message, see
+    /// [`ConnectorMessage::from_netlink`].
+    pub fn from_netlink(
+        message: NetlinkMessage<ConnectorMessageBatch>,
+    ) -> Result<Self, DeserializeError> {
+        let (header, payload) = message.into_parts();
+        match payload {
+            NetlinkPayload::InnerMessage(batch) => Ok(batch),
+            NetlinkPayload::Done(done) => {
+                let mut payload = done.code.to_ne_bytes().to_vec();
+                payload.extend_from_slice(&done.extended_ack);
+                ConnectorMessageBatch::deserialize(&header, &payload)
+            }
+            _ => Err(DeserializeError::UnexpectedMessageType {
+                message_type: header.message_type,
+            }),
+        }
+    }
+
+    /// Check that every message can be serialized and that the batch
+    /// fits in [`CONNECTOR_MAX_MSG_SIZE`](super::CONNECTOR_MAX_MSG_SIZE).
+    pub fn validate(&self) -> Result<(), SerializeError> {
+        for message in &self.messages {
+            message.validate()?;
+        }
+        check_message_size(self.buffer_len())
+    }
+
+    /// Serialize the batch into `buffer`, which must be exactly
+    /// [`NetlinkSerializable::buffer_len`] bytes long, writing each record
+    /// in the byte order of its [`ConnectorMessage::endianness`].
+    pub fn try_emit(&self, buffer: &mut [u8]) -> Result<(), SerializeError> {
+        self.emit(buffer, ConnectorMessage::endianness)
+    }
+
+    /// Like [`Self::try_emit`], writing the fields of every record in the
+    /// given byte order.
+    pub fn try_emit_with_endianness(
+        &self,
+        buffer: &mut [u8],
+        endianness: Endianness,
+    ) -> Result<(), SerializeError> {
+        self.emit(buffer, |_| endianness)
+    }
+
+    fn emit(
+        &self,
+        buffer: &mut [u8],
+        endianness: impl Fn(&ConnectorMessage) -> Endianness,
+    ) -> Result<(), SerializeError> {
+        self.validate()?;
+        emit_padded(buffer, self.buffer_len(), |buffer| {
+            self.messages.iter().fold(0, |offset, message| {
+                let endianness = endianness(message);
+                offset + message.emit_record(&mut buffer[offset..], endianness)
+            })
+        })
+    }
+
+    // Records are not padded, only the batch as a whole is aligned.
+    fn records_len(&self) -> usize {
+        self.messages
+            .iter()
+            .map(|message| CONNECTOR_HEADER_LEN + message.data().len())
+            .sum()
+    }
+}
+
+impl From<Vec<ConnectorMessage>> for ConnectorMessageBatch {
+    fn from(messages: Vec<ConnectorMessage>) -> Self {
+        ConnectorMessageBatch {
+            message_type: NLMSG_DONE,
+            messages,
+        }
+    }
+}
+
+impl FromIterator<ConnectorMessage> for ConnectorMessageBatch {
+    fn from_iter<I: IntoIterator<Item = ConnectorMessage>>(iter: I) -> Self {
+        ConnectorMessageBatch::from(iter.into_iter().collect::<Vec<_>>())
+    }
+}
+
+impl IntoIterator for ConnectorMessageBatch {
+    type Item = ConnectorMessage;
+    type IntoIter = std::vec::IntoIter<ConnectorMessage>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.messages.into_iter()
+    }
+}
+
+// NetlinkDeserializable implementation
+impl NetlinkDeserializable for ConnectorMessageBatch {
+    type Error = DeserializeError;
+
+    fn deserialize(
+        header: &NetlinkHeader,
+        payload: &[u8],
+    ) -> Result<Self, Self::Error> {
+        let message_type = check_message_type(header.message_type)?;
+        Ok(ConnectorMessageBatch {
+            message_type,
+            messages: ConnectorMessages::new(payload)
+                .map(|record| {
+                    record
+                        .map(|message| message.with_message_type(message_type))
+                })
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+// NetlinkSerializable implementation
+impl NetlinkSerializable for ConnectorMessageBatch {
+    fn message_type(&self) -> u16 {
+        self.message_type
+    }
+
+    fn buffer_len(&self) -> usize {
+        nlmsg_align(self.records_len())
+    }
+
+    // Panics on invalid batches, see the `ConnectorMessage`
+    // implementation.
+    fn serialize(&self, buffer: &mut [u8]) {
+        if let Err(e) = self.try_emit(buffer) {
+            panic!("cannot serialize connector message batch: {e}");
+        }
+    }
+}
+
+impl From<ConnectorMessageBatch> for NetlinkPayload<ConnectorMessageBatch> {
+    fn from(batch: ConnectorMessageBatch) -> Self {
+        NetlinkPayload::InnerMessage(batch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Records with odd data lengths, so none but the first starts
+    // aligned.
+    fn batch() -> ConnectorMessageBatch {
+        [1, 3, 5]
+            .into_iter()
+            .enumerate()
+            .map(|(i, len)| {
+                let data: Vec<u8> = (1..=len).collect();
+                ConnectorMessage::new(1, 1, i as u32, 0, 0, data)
+            })
+            .collect()
+    }
+
+    fn emit(batch: &ConnectorMessageBatch) -> Vec<u8> {
+        let mut bytes = vec![0xff; batch.buffer_len()];
+        batch.try_emit(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn emit_parse_round_trip() {
+        let batch = batch();
+        let bytes = emit(&batch);
+        assert_eq!(bytes.len(), nlmsg_align(3 * CONNECTOR_HEADER_LEN + 9));
+        assert_eq!(bytes[3 * CONNECTOR_HEADER_LEN + 9..], [0; 3]);
+
+        let messages = ConnectorMessages::new(&bytes)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(messages, batch.messages());
+        assert_eq!(ConnectorMessageBatch::parse(&bytes), Ok(batch));
+    }
+
+    #[test]
+    fn truncated_record_after_valid_one() {
+        let batch = batch();
+        let bytes = emit(&batch);
+        // cut the second record in the middle of its data.
+        let end = 2 * CONNECTOR_HEADER_LEN + 1 + 1;
+        let mut records = ConnectorMessages::new(&bytes[..end]);
+
+        assert_eq!(records.next(), Some(Ok(batch.messages()[0].clone())));
+        assert_eq!(
+            records.next(),
+            Some(Err(DeserializeError::LengthExceedsPayload {
+                len: 3,
+                available: 1,
+            })),
+        );
+        assert_eq!(records.next(), None);
+    }
+
+    #[test]
+    fn netlink_round_trip() {
+        let batch = batch();
+        let mut message = NetlinkMessage::from(batch.clone());
+        message.finalize();
+        let mut bytes = vec![0; message.buffer_len()];
+        message.serialize(&mut bytes);
+
+        let message =
+            NetlinkMessage::<ConnectorMessageBatch>::deserialize(&bytes)
+                .unwrap();
+        assert!(matches!(message.payload, NetlinkPayload::Done(_)));
+        assert_eq!(ConnectorMessageBatch::from_netlink(message), Ok(batch));
+    }
+
+    #[test]
+    fn records_keep_their_byte_order() {
+        let batch = batch();
+        let mut bytes = vec![0; batch.buffer_len()];
+        batch
+            .try_emit_with_endianness(&mut bytes, Endianness::Big)
+            .unwrap();
+
+        let parsed =
+            ConnectorMessages::with_endianness(&bytes, Endianness::Big)
+                .collect::<Result<ConnectorMessageBatch, _>>()
+                .unwrap();
+        assert_eq!(parsed.messages()[1].seq(), 1);
+        assert_eq!(emit(&parsed), bytes);
+    }
+}
@@ -0,0 +1,343 @@
+use netlink_packet_core::{NetlinkPayload, NetlinkSerializable, NLMSG_DONE};
+
+use super::{
+    check_record, emit_header, emit_padded, nlmsg_align, ConnectorId,
+    ConnectorMessage, ConnectorMessageBuffer, ConnectorPayload,
+    DeserializeError, Endianness, ParseMode, SerializeError,
+    CONNECTOR_HEADER_LEN, MAX_DATA_AREA,
+};
+
+/// A connector message whose data borrows from the receive buffer.
+///
+/// Parsing one does not allocate, so messages can be inspected and
+/// dropped cheaply; [`ConnectorMessageRef::to_owned`] converts the
+/// ones worth keeping into a [`ConnectorMessage`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct ConnectorMessageRef<'a> {
+    message_type: u16,
+    id: ConnectorId,
+    seq: u32,
+    ack: u32,
+    flags: u16,
+    data: &'a [u8],
+    // The byte order the message was parsed with, and its data is in.
+    endianness: Endianness,
+}
+
+impl<'a> ConnectorMessageRef<'a> {
+    pub fn new(
+        idx: u32,
+        value: u32,
+        seq: u32,
+        ack: u32,
+        flags: u16,
+        data: &'a [u8],
+    ) -> Self {
+        ConnectorMessageRef {
+            message_type: NLMSG_DONE,
+            id: ConnectorId::new(idx, value),
+            seq,
+            ack,
+            flags,
+            data,
+            endianness: Endianness::Native,
+        }
+    }
+
+    /// Replace the netlink message type, see
+    /// [`ConnectorMessage::with_message_type`].
+    pub fn with_message_type(mut self, message_type: u16) -> Self {
+        self.message_type = message_type;
+        self
+    }
+
+    // Set the byte order the data is in, for messages borrowed from an
+    // owned one.
+    pub(super) fn with_endianness(mut self, endianness: Endianness) -> Self {
+        self.endianness = endianness;
+        self
+    }
+
+    pub fn id(&self) -> ConnectorId {
+        self.id
+    }
+
+    pub fn idx(&self) -> u32 {
+        self.id.idx
+    }
+
+    pub fn value(&self) -> u32 {
+        self.id.value
+    }
+
+    pub fn seq(&self) -> u32 {
+        self.seq
+    }
+
+    pub fn ack(&self) -> u32 {
+        self.ack
+    }
+
+    pub fn flags(&self) -> u16 {
+        self.flags
+    }
+
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// The byte order of the data, the one the message was parsed with.
+    pub fn endianness(&self) -> Endianness {
+        self.endianness
+    }
+
+    /// Parse a `struct cn_msg` and the data following it without
+    /// copying the data, checking the padding in [`ParseMode::Strict`]
+    /// mode.
+    ///
+    /// Every access is bounds checked, so malformed input yields a
+    /// [`DeserializeError`] rather than a panic.
+    pub fn parse(payload: &'a [u8]) -> Result<Self, DeserializeError> {
+        Self::parse_with_mode(payload, ParseMode::Strict)
+    }
+
+    /// Parse a `struct cn_msg` without copying the data, checking the
+    /// padding according to `mode`.
+    pub fn parse_with_mode(
+        payload: &'a [u8],
+        mode: ParseMode,
+    ) -> Result<Self, DeserializeError> {
+        Self::parse_buffer(ConnectorMessageBuffer::new(payload), mode)
+    }
+
+    /// Parse the message in `buf` without copying the data, reading its
+    /// fields in the buffer's byte order and checking the padding
+    /// according to `mode`.
+    pub fn parse_buffer(
+        buf: ConnectorMessageBuffer<&'a [u8]>,
+        mode: ParseMode,
+    ) -> Result<Self, DeserializeError> {
+        let endianness = buf.endianness();
+        let payload = buf.into_inner();
+        let buf = ConnectorMessageBuffer::new_checked_with_endianness(
+            payload, endianness,
+        )?;
+        let data_area = payload.len() - CONNECTOR_HEADER_LEN;
+        if data_area > MAX_DATA_AREA {
+            return Err(DeserializeError::PayloadTooLarge {
+                size: data_area,
+                max: MAX_DATA_AREA,
+            });
+        }
+
+        // the data space is padded to 4 byte blocks.
+        let end = CONNECTOR_HEADER_LEN + buf.len() as usize;
+        mode.check_padding(buf.len(), buf.trailer(), end)?;
+
+        Ok(ConnectorMessageRef {
+            message_type: NLMSG_DONE,
+            id: ConnectorId::new(buf.idx(), buf.value()),
+            seq: buf.seq(),
+            ack: buf.ack(),
+            flags: buf.flags(),
+            data: &payload[CONNECTOR_HEADER_LEN..end],
+            endianness,
+        })
+    }
+
+    /// Check that this message can be serialized, see
+    /// [`ConnectorMessage::validate`].
+    pub fn validate(&self) -> Result<(), SerializeError> {
+        check_record(self.data.len())
+    }
+
+    /// Serialize this message into `buffer`, see
+    /// [`ConnectorMessage::try_emit`].
+    pub fn try_emit(&self, buffer: &mut [u8]) -> Result<(), SerializeError> {
+        self.try_emit_with_endianness(buffer, self.endianness)
+    }
+
+    /// Like [`Self::try_emit`], writing the fields in the given byte
+    /// order.
+    pub fn try_emit_with_endianness(
+        &self,
+        buffer: &mut [u8],
+        endianness: Endianness,
+    ) -> Result<(), SerializeError> {
+        self.validate()?;
+        emit_padded(buffer, self.buffer_len(), |buffer| {
+            let header = (self.id, self.seq, self.ack, self.flags);
+            emit_header(buffer, endianness, header, self.data.len())
+                .data_mut()
+                .copy_from_slice(self.data);
+            CONNECTOR_HEADER_LEN + self.data.len()
+        })
+    }
+
+    /// Copy the data into an owned [`ConnectorMessage`], which keeps the
+    /// byte order of the data.
+    pub fn to_owned(&self) -> ConnectorMessage {
+        ConnectorMessage {
+            message_type: self.message_type,
+            id: self.id,
+            seq: self.seq,
+            ack: self.ack,
+            flags: self.flags,
+            payload: self.data.to_vec(),
+            endianness: self.endianness,
+        }
+    }
+
+    /// Decode the data as a typed payload, checking that the message is
+    /// addressed to the payload's connector.
+    ///
+    /// The data is read in the byte order the header was parsed with,
+    /// see [`Self::endianness`].
+    pub fn decode<P: ConnectorPayload>(
+        &self,
+    ) -> Result<ConnectorMessage<P>, DeserializeError> {
+        self.decode_with_endianness(self.endianness)
+    }
+
+    /// Like [`Self::decode`], for data in the given byte order.
+    pub fn decode_with_endianness<P: ConnectorPayload>(
+        &self,
+        endianness: Endianness,
+    ) -> Result<ConnectorMessage<P>, DeserializeError> {
+        if let Some(expected) = P::ID {
+            if expected != self.id {
+                return Err(DeserializeError::IdMismatch {
+                    expected,
+                    got: self.id,
+                });
+            }
+        }
+        Ok(ConnectorMessage {
+            message_type: self.message_type,
+            id: self.id,
+            seq: self.seq,
+            ack: self.ack,
+            flags: self.flags,
+            payload: P::parse_payload(self.data, endianness)?,
+            endianness,
+        })
+    }
+}
+
+impl<'a> From<&'a ConnectorMessage> for ConnectorMessageRef<'a> {
+    fn from(message: &'a ConnectorMessage) -> Self {
+        message.as_borrowed()
+    }
+}
+
+// NetlinkSerializable implementation
+impl NetlinkSerializable for ConnectorMessageRef<'_> {
+    fn message_type(&self) -> u16 {
+        self.message_type
+    }
+
+    // The data is padded so that the next message starts aligned.
+    fn buffer_len(&self) -> usize {
+        CONNECTOR_HEADER_LEN + nlmsg_align(self.data.len())
+    }
+
+    // Panics on invalid messages, see the `ConnectorMessage`
+    // implementation.
+    fn serialize(&self, buffer: &mut [u8]) {
+        if let Err(e) = self.try_emit(buffer) {
+            panic!("cannot serialize connector message: {e}");
+        }
+    }
+}
+
+impl<'a> From<ConnectorMessageRef<'a>>
+    for NetlinkPayload<ConnectorMessageRef<'a>>
+{
+    fn from(message: ConnectorMessageRef<'a>) -> Self {
+        NetlinkPayload::InnerMessage(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::CONNECTOR_MAX_MSG_SIZE;
+
+    #[test]
+    fn validate_errors() {
+        let data = vec![0; 65536];
+        let message = ConnectorMessageRef::new(1, 1, 0, 0, 0, &data);
+        assert_eq!(
+            message.validate(),
+            Err(SerializeError::LengthOverflow { len: 65536 }),
+        );
+
+        let message = ConnectorMessageRef::new(1, 1, 0, 0, 0, &data[..20000]);
+        assert_eq!(
+            message.validate(),
+            Err(SerializeError::MessageTooLarge {
+                size: CONNECTOR_HEADER_LEN + 20000,
+                max: CONNECTOR_MAX_MSG_SIZE,
+            }),
+        );
+        let mut bytes = vec![0; message.buffer_len()];
+        assert_eq!(message.try_emit(&mut bytes), message.validate());
+
+        let message = ConnectorMessageRef::new(1, 1, 0, 0, 0, &data[..3]);
+        assert_eq!(
+            message.try_emit(&mut [0; 23]),
+            Err(SerializeError::BufferSize {
+                expected: 24,
+                got: 23,
+            }),
+        );
+    }
+
+    #[test]
+    fn try_emit_zeroes_padding() {
+        for len in 0..=8 {
+            let data: Vec<u8> = (1..=len as u8).collect();
+            let message = ConnectorMessageRef::new(1, 1, 7, 8, 0, &data);
+            let mut bytes = vec![0xff; message.buffer_len()];
+            message.try_emit(&mut bytes).unwrap();
+
+            assert_eq!(bytes.len(), CONNECTOR_HEADER_LEN + nlmsg_align(len));
+            let padding = &bytes[CONNECTOR_HEADER_LEN + len..];
+            assert!(padding.iter().all(|byte| *byte == 0));
+            assert_eq!(ConnectorMessageRef::parse(&bytes), Ok(message));
+        }
+    }
+
+    #[test]
+    fn serializes_like_the_owned_message() {
+        for len in 0..=8 {
+            let data: Vec<u8> = (1..=len as u8).collect();
+            let owned = ConnectorMessage::new(3, 1, 7, 8, 0x10, data)
+                .with_message_type(0x20);
+            let borrowed = owned.as_borrowed();
+            assert_eq!(borrowed.message_type(), owned.message_type());
+            assert_eq!(borrowed.buffer_len(), owned.buffer_len());
+
+            let mut owned_bytes = vec![0; owned.buffer_len()];
+            owned.serialize(&mut owned_bytes);
+            let mut borrowed_bytes = vec![0xff; borrowed.buffer_len()];
+            borrowed.serialize(&mut borrowed_bytes);
+            assert_eq!(borrowed_bytes, owned_bytes);
+        }
+    }
+
+    #[test]
+    fn to_owned_round_trip() {
+        let owned = ConnectorMessage::new(3, 1, 7, 8, 0x10, vec![1, 2, 3])
+            .with_message_type(0x20);
+        let borrowed = ConnectorMessageRef::from(&owned);
+        assert_eq!(borrowed.data(), owned.data());
+        assert_eq!(borrowed.to_owned(), owned);
+
+        let mut bytes = vec![0; owned.buffer_len()];
+        owned.try_emit(&mut bytes).unwrap();
+        let parsed = ConnectorMessageRef::parse(&bytes).unwrap();
+        assert_eq!(parsed.to_owned().as_borrowed(), parsed);
+        assert_eq!(parsed.to_owned(), owned.with_message_type(NLMSG_DONE));
+    }
+}
@@ -0,0 +1,282 @@
+use std::ops::{Range, RangeFrom};
+
+use super::{DeserializeError, Endianness};
+
+type Field = Range<usize>;
+type Rest = RangeFrom<usize>;
+
+const IDX: Field = 0..4;
+const VALUE: Field = 4..8;
+const SEQ: Field = 8..12;
+const ACK: Field = 12..16;
+const LEN: Field = 16..18;
+const FLAGS: Field = 18..20;
+const DATA: Rest = 20..;
+
+/// Length of the `struct cn_msg` header preceding the data.
+pub const CONNECTOR_HEADER_LEN: usize = DATA.start;
+
+/// A raw `struct cn_msg` buffer with getters and setters for the
+/// header fields, giving zero-copy access to a message in place.
+///
+/// Fields are read and written in the buffer's [`Endianness`], which is
+/// the host byte order unless chosen otherwise.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ConnectorMessageBuffer<T> {
+    buffer: T,
+    endianness: Endianness,
+}
+
+impl<T: AsRef<[u8]>> ConnectorMessageBuffer<T> {
+    /// Wrap a buffer without checking its length. The getters panic
+    /// if the buffer is too short, use [`Self::new_checked`] for
+    /// untrusted input.
+    pub fn new(buffer: T) -> ConnectorMessageBuffer<T> {
+        Self::with_endianness(buffer, Endianness::Native)
+    }
+
+    /// Like [`Self::new`], for a buffer in the given byte order.
+    pub fn with_endianness(
+        buffer: T,
+        endianness: Endianness,
+    ) -> ConnectorMessageBuffer<T> {
+        ConnectorMessageBuffer { buffer, endianness }
+    }
+
+    /// Wrap a buffer, checking that it holds a complete header and
+    /// the amount of data announced by `len`.
+    pub fn new_checked(
+        buffer: T,
+    ) -> Result<ConnectorMessageBuffer<T>, DeserializeError> {
+        Self::new_checked_with_endianness(buffer, Endianness::Native)
+    }
+
+    /// Like [`Self::new_checked`], for a buffer in the given byte order.
+    pub fn new_checked_with_endianness(
+        buffer: T,
+        endianness: Endianness,
+    ) -> Result<ConnectorMessageBuffer<T>, DeserializeError> {
+        let packet = Self::with_endianness(buffer, endianness);
+        packet.check_buffer_length()?;
+        Ok(packet)
+    }
+
+    fn check_buffer_length(&self) -> Result<(), DeserializeError> {
+        let got = self.buffer.as_ref().len();
+        if got < CONNECTOR_HEADER_LEN {
+            return Err(DeserializeError::Truncated {
+                needed: CONNECTOR_HEADER_LEN,
+                got,
+            });
+        }
+        let len = self.len();
+        let available = got - CONNECTOR_HEADER_LEN;
+        if len as usize > available {
+            return Err(DeserializeError::LengthExceedsPayload {
+                len,
+                available,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn endianness(&self) -> Endianness {
+        self.endianness
+    }
+
+    /// Consume the packet, returning the underlying buffer.
+    pub fn into_inner(self) -> T {
+        self.buffer
+    }
+
+    pub fn idx(&self) -> u32 {
+        let data = self.buffer.as_ref();
+        self.endianness.read_u32(&data[IDX])
+    }
+
+    pub fn value(&self) -> u32 {
+        let data = self.buffer.as_ref();
+        self.endianness.read_u32(&data[VALUE])
+    }
+
+    pub fn seq(&self) -> u32 {
+        let data = self.buffer.as_ref();
+        self.endianness.read_u32(&data[SEQ])
+    }
+
+    pub fn ack(&self) -> u32 {
+        let data = self.buffer.as_ref();
+        self.endianness.read_u32(&data[ACK])
+    }
+
+    pub fn len(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        self.endianness.read_u16(&data[LEN])
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn flags(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        self.endianness.read_u16(&data[FLAGS])
+    }
+
+    /// The `len` bytes of data following the header.
+    pub fn data(&self) -> &[u8] {
+        let end = DATA.start + self.len() as usize;
+        &self.buffer.as_ref()[DATA.start..end]
+    }
+
+    /// Everything after the data, i.e. padding or further records.
+    pub fn trailer(&self) -> &[u8] {
+        let start = DATA.start + self.len() as usize;
+        &self.buffer.as_ref()[start..]
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> ConnectorMessageBuffer<T> {
+    pub fn set_idx(&mut self, value: u32) {
+        let data = self.buffer.as_mut();
+        self.endianness.write_u32(&mut data[IDX], value)
+    }
+
+    pub fn set_value(&mut self, value: u32) {
+        let data = self.buffer.as_mut();
+        self.endianness.write_u32(&mut data[VALUE], value)
+    }
+
+    pub fn set_seq(&mut self, value: u32) {
+        let data = self.buffer.as_mut();
+        self.endianness.write_u32(&mut data[SEQ], value)
+    }
+
+    pub fn set_ack(&mut self, value: u32) {
+        let data = self.buffer.as_mut();
+        self.endianness.write_u32(&mut data[ACK], value)
+    }
+
+    pub fn set_len(&mut self, value: u16) {
+        let data = self.buffer.as_mut();
+        self.endianness.write_u16(&mut data[LEN], value)
+    }
+
+    pub fn set_flags(&mut self, value: u16) {
+        let data = self.buffer.as_mut();
+        self.endianness.write_u16(&mut data[FLAGS], value)
+    }
+
+    /// Mutable access to the `len` bytes of data following the header.
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        let end = DATA.start + self.len() as usize;
+        &mut self.buffer.as_mut()[DATA.start..end]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::ConnectorMessage;
+
+    #[test]
+    fn new_checked_rejects_short_buffers() {
+        let bytes = [0; CONNECTOR_HEADER_LEN];
+        for len in 0..CONNECTOR_HEADER_LEN {
+            assert_eq!(
+                ConnectorMessageBuffer::new_checked(&bytes[..len]),
+                Err(DeserializeError::Truncated {
+                    needed: CONNECTOR_HEADER_LEN,
+                    got: len,
+                }),
+            );
+        }
+        assert!(ConnectorMessageBuffer::new_checked(&bytes[..]).is_ok());
+    }
+
+    #[test]
+    fn new_checked_rejects_long_len() {
+        let mut bytes = [0; CONNECTOR_HEADER_LEN + 4];
+        ConnectorMessageBuffer::new(&mut bytes[..]).set_len(5);
+        assert_eq!(
+            ConnectorMessageBuffer::new_checked(&bytes[..]),
+            Err(DeserializeError::LengthExceedsPayload {
+                len: 5,
+                available: 4,
+            }),
+        );
+
+        ConnectorMessageBuffer::new(&mut bytes[..]).set_len(4);
+        let buf = ConnectorMessageBuffer::new_checked(&bytes[..]).unwrap();
+        assert_eq!(buf.data(), [0; 4]);
+        assert!(buf.trailer().is_empty());
+    }
+
+    // Rewrite the header of a message in the receive buffer it arrived
+    // in, behind its netlink header and followed by unused space.
+    #[test]
+    fn setters_rewrite_in_place() {
+        let message = ConnectorMessage::new(1, 1, 7, 8, 0, vec![1, 2, 3, 4]);
+        let mut recv = [0xaa; 64];
+        message.try_emit(&mut recv[16..40]).unwrap();
+
+        let mut buf =
+            ConnectorMessageBuffer::new_checked(&mut recv[16..]).unwrap();
+        buf.set_idx(3);
+        buf.set_value(2);
+        buf.set_seq(9);
+        buf.set_ack(10);
+        buf.set_flags(0x11);
+        buf.data_mut().copy_from_slice(&[5, 6, 7, 8]);
+        assert_eq!(buf.trailer().len(), 64 - 16 - CONNECTOR_HEADER_LEN - 4);
+
+        assert_eq!(recv[..16], [0xaa; 16]);
+        assert_eq!(recv[40..], [0xaa; 24]);
+        assert_eq!(
+            ConnectorMessage::parse(&recv[16..40]),
+            Ok(ConnectorMessage::new(3, 2, 9, 10, 0x11, vec![5, 6, 7, 8])),
+        );
+    }
+
+    #[test]
+    fn fields_in_foreign_byte_order() {
+        let mut bytes = [0; CONNECTOR_HEADER_LEN + 2];
+        for (endianness, seq) in [
+            (Endianness::Big, [1, 2, 3, 4]),
+            (Endianness::Little, [4, 3, 2, 1]),
+        ] {
+            let mut buf = ConnectorMessageBuffer::with_endianness(
+                &mut bytes[..],
+                endianness,
+            );
+            buf.set_idx(0x0a0b_0c0d);
+            buf.set_value(7);
+            buf.set_seq(0x0102_0304);
+            buf.set_ack(0x0102_0305);
+            buf.set_len(2);
+            buf.set_flags(0x0506);
+            assert_eq!(bytes[8..12], seq);
+
+            let buf = ConnectorMessageBuffer::new_checked_with_endianness(
+                &bytes[..],
+                endianness,
+            )
+            .unwrap();
+            assert_eq!(buf.endianness(), endianness);
+            assert_eq!(buf.idx(), 0x0a0b_0c0d);
+            assert_eq!(buf.value(), 7);
+            assert_eq!(buf.seq(), 0x0102_0304);
+            assert_eq!(buf.ack(), 0x0102_0305);
+            assert_eq!(buf.len(), 2);
+            assert_eq!(buf.flags(), 0x0506);
+        }
+
+        // the same bytes read in the other order.
+        let buf = ConnectorMessageBuffer::with_endianness(
+            &bytes[..],
+            Endianness::Big,
+        );
+        assert_eq!(buf.seq(), 0x0403_0201);
+        assert_eq!(buf.len(), 0x0200);
+    }
+}
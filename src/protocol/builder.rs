@@ -0,0 +1,205 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use netlink_packet_core::NLMSG_DONE;
+
+use super::{ConnectorId, ConnectorMessage, ConnectorPayload};
+
+/// Builder for [`ConnectorMessage`], naming each header field so that
+/// `seq` and `ack` cannot be swapped by accident. Unset fields are zero,
+/// the data is empty and the message type is [`NLMSG_DONE`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ConnectorMessageBuilder<P = Vec<u8>> {
+    message_type: u16,
+    idx: u32,
+    value: u32,
+    seq: u32,
+    ack: u32,
+    flags: u16,
+    payload: P,
+}
+
+impl Default for ConnectorMessageBuilder {
+    fn default() -> Self {
+        ConnectorMessageBuilder {
+            message_type: NLMSG_DONE,
+            idx: 0,
+            value: 0,
+            seq: 0,
+            ack: 0,
+            flags: 0,
+            payload: Vec::new(),
+        }
+    }
+}
+
+impl ConnectorMessageBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn data(mut self, data: impl Into<Vec<u8>>) -> Self {
+        self.payload = data.into();
+        self
+    }
+}
+
+impl<P: ConnectorPayload> ConnectorMessageBuilder<P> {
+    pub fn message_type(mut self, message_type: u16) -> Self {
+        self.message_type = message_type;
+        self
+    }
+
+    pub fn id(mut self, id: ConnectorId) -> Self {
+        self.idx = id.idx;
+        self.value = id.value;
+        self
+    }
+
+    pub fn idx(mut self, idx: u32) -> Self {
+        self.idx = idx;
+        self
+    }
+
+    pub fn value(mut self, value: u32) -> Self {
+        self.value = value;
+        self
+    }
+
+    pub fn seq(mut self, seq: u32) -> Self {
+        self.seq = seq;
+        self
+    }
+
+    pub fn ack(mut self, ack: u32) -> Self {
+        self.ack = ack;
+        self
+    }
+
+    pub fn flags(mut self, flags: u16) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    /// Carry a typed payload, addressing the message to the payload's
+    /// [`ConnectorPayload::ID`] if it has one.
+    pub fn payload<Q: ConnectorPayload>(
+        self,
+        payload: Q,
+    ) -> ConnectorMessageBuilder<Q> {
+        let id = Q::ID.unwrap_or(ConnectorId::new(self.idx, self.value));
+        ConnectorMessageBuilder {
+            message_type: self.message_type,
+            idx: id.idx,
+            value: id.value,
+            seq: self.seq,
+            ack: self.ack,
+            flags: self.flags,
+            payload,
+        }
+    }
+
+    /// Address the message as a reply to `request`: same id and `seq`,
+    /// and `ack` set to the request's `seq + 1` as the connector
+    /// protocol expects.
+    pub fn reply_to<Q: ConnectorPayload>(
+        self,
+        request: &ConnectorMessage<Q>,
+    ) -> Self {
+        self.id(request.id())
+            .seq(request.seq())
+            .ack(request.seq().wrapping_add(1))
+    }
+
+    pub fn build(self) -> ConnectorMessage<P> {
+        ConnectorMessage::from_payload(self.payload)
+            .with_message_type(self.message_type)
+            .with_id(ConnectorId::new(self.idx, self.value))
+            .with_seq(self.seq)
+            .with_ack(self.ack)
+            .with_flags(self.flags)
+    }
+}
+
+/// Hands out increasing sequence numbers for request messages.
+///
+/// The generator is lock-free and can be shared between threads, for
+/// instance in a `static` or behind an `Arc`. Numbers wrap around after
+/// `u32::MAX`.
+#[derive(Debug, Default)]
+pub struct SequenceGenerator {
+    next: AtomicU32,
+}
+
+impl SequenceGenerator {
+    /// Create a generator whose first number is 0.
+    pub const fn new() -> Self {
+        Self::starting_at(0)
+    }
+
+    /// Create a generator whose first number is `seq`.
+    pub const fn starting_at(seq: u32) -> Self {
+        SequenceGenerator {
+            next: AtomicU32::new(seq),
+        }
+    }
+
+    /// Return the next sequence number.
+    pub fn next_seq(&self) -> u32 {
+        self.next.fetch_add(1, Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use netlink_packet_core::NetlinkSerializable;
+
+    use super::*;
+
+    #[test]
+    fn defaults() {
+        let message = ConnectorMessage::builder().build();
+        assert_eq!(message, ConnectorMessage::new(0, 0, 0, 0, 0, Vec::new()));
+        assert_eq!(message.message_type(), NLMSG_DONE);
+    }
+
+    #[test]
+    fn named_fields() {
+        let message = ConnectorMessage::builder()
+            .id(ConnectorId::PROC)
+            .seq(7)
+            .ack(8)
+            .flags(9)
+            .data([1, 2, 3])
+            .build();
+        assert_eq!(
+            message,
+            ConnectorMessage::new(1, 1, 7, 8, 9, vec![1, 2, 3]),
+        );
+    }
+
+    #[test]
+    fn reply_to() {
+        for seq in [0, 41, u32::MAX] {
+            let request = ConnectorMessage::new(3, 1, seq, 0, 0, Vec::new());
+            let reply = ConnectorMessage::builder().reply_to(&request).build();
+            assert_eq!(reply.id(), request.id());
+            assert_eq!(reply.seq(), seq);
+            assert_eq!(reply.ack(), seq.wrapping_add(1));
+        }
+        let request = ConnectorMessage::new(3, 1, u32::MAX, 0, 0, Vec::new());
+        let reply = ConnectorMessage::builder().reply_to(&request).build();
+        assert_eq!(reply.ack(), 0);
+    }
+
+    #[test]
+    fn sequence_generator() {
+        let generator = SequenceGenerator::starting_at(u32::MAX - 1);
+        assert_eq!(generator.next_seq(), u32::MAX - 1);
+        assert_eq!(generator.next_seq(), u32::MAX);
+        assert_eq!(generator.next_seq(), 0);
+
+        let generator = SequenceGenerator::new();
+        assert_eq!(generator.next_seq(), 0);
+        assert_eq!(generator.next_seq(), 1);
+    }
+}
@@ -0,0 +1,58 @@
+use byteorder::{BigEndian, ByteOrder, LittleEndian, NativeEndian};
+
+/// Byte order of the integer fields of a message.
+///
+/// Messages on a live socket are always in the host byte order, the
+/// other variants are for decoding captures taken on other machines.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub enum Endianness {
+    #[default]
+    Native,
+    Little,
+    Big,
+}
+
+// Dispatch a byteorder call on the selected byte order.
+macro_rules! dispatch {
+    ($endianness:expr, $method:ident($($arg:expr),*)) => {
+        match $endianness {
+            Endianness::Native => NativeEndian::$method($($arg),*),
+            Endianness::Little => LittleEndian::$method($($arg),*),
+            Endianness::Big => BigEndian::$method($($arg),*),
+        }
+    };
+}
+
+impl Endianness {
+    pub fn read_u16(self, buf: &[u8]) -> u16 {
+        dispatch!(self, read_u16(buf))
+    }
+
+    pub fn read_u32(self, buf: &[u8]) -> u32 {
+        dispatch!(self, read_u32(buf))
+    }
+
+    pub fn read_u64(self, buf: &[u8]) -> u64 {
+        dispatch!(self, read_u64(buf))
+    }
+
+    pub fn read_i32(self, buf: &[u8]) -> i32 {
+        dispatch!(self, read_i32(buf))
+    }
+
+    pub fn write_u16(self, buf: &mut [u8], n: u16) {
+        dispatch!(self, write_u16(buf, n))
+    }
+
+    pub fn write_u32(self, buf: &mut [u8], n: u32) {
+        dispatch!(self, write_u32(buf, n))
+    }
+
+    pub fn write_u64(self, buf: &mut [u8], n: u64) {
+        dispatch!(self, write_u64(buf, n))
+    }
+
+    pub fn write_i32(self, buf: &mut [u8], n: i32) {
+        dispatch!(self, write_i32(buf, n))
+    }
+}
@@ -0,0 +1,209 @@
+use std::{error::Error, fmt, str::FromStr};
+
+pub const CN_IDX_PROC: u32 = 0x1;
+pub const CN_VAL_PROC: u32 = 0x1;
+pub const CN_IDX_CIFS: u32 = 0x2;
+pub const CN_VAL_CIFS: u32 = 0x1;
+/// w1 communication
+pub const CN_W1_IDX: u32 = 0x3;
+pub const CN_W1_VAL: u32 = 0x1;
+pub const CN_IDX_V86D: u32 = 0x4;
+pub const CN_VAL_V86D_UVESAFB: u32 = 0x2;
+/// BlackBoard, from the TSP GPL sampling framework
+pub const CN_IDX_BB: u32 = 0x5;
+pub const CN_DST_IDX: u32 = 0x6;
+pub const CN_DST_VAL: u32 = 0x1;
+/// Device Mapper
+pub const CN_IDX_DM: u32 = 0x7;
+pub const CN_VAL_DM_USERSPACE_LOG: u32 = 0x1;
+pub const CN_IDX_DRBD: u32 = 0x8;
+pub const CN_VAL_DRBD: u32 = 0x1;
+/// HyperV KVP
+pub const CN_KVP_IDX: u32 = 0x9;
+/// queries from the kernel
+pub const CN_KVP_VAL: u32 = 0x1;
+/// HyperV VSS
+pub const CN_VSS_IDX: u32 = 0xA;
+/// queries from the kernel
+pub const CN_VSS_VAL: u32 = 0x1;
+/// Highest index + 1
+pub const CN_NETLINK_USERS: u32 = 11;
+
+/// Identity of a connector user, the `struct cb_id` of the kernel.
+///
+/// Messages are routed by this `idx`/`value` pair. The associated
+/// constants name the pairs registered by in-tree kernel users.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct ConnectorId {
+    pub idx: u32,
+    pub value: u32,
+}
+
+impl ConnectorId {
+    /// Process events, `CN_IDX_PROC`/`CN_VAL_PROC`.
+    pub const PROC: ConnectorId = ConnectorId::new(CN_IDX_PROC, CN_VAL_PROC);
+    /// CIFS upcalls, `CN_IDX_CIFS`/`CN_VAL_CIFS`.
+    pub const CIFS: ConnectorId = ConnectorId::new(CN_IDX_CIFS, CN_VAL_CIFS);
+    /// 1-Wire bus, `CN_W1_IDX`/`CN_W1_VAL`.
+    pub const W1: ConnectorId = ConnectorId::new(CN_W1_IDX, CN_W1_VAL);
+    /// uvesafb helper, `CN_IDX_V86D`/`CN_VAL_V86D_UVESAFB`.
+    pub const V86D_UVESAFB: ConnectorId =
+        ConnectorId::new(CN_IDX_V86D, CN_VAL_V86D_UVESAFB);
+    /// BlackBoard, `CN_IDX_BB`. The kernel defines no value for this
+    /// index, so it is 0 here.
+    pub const BB: ConnectorId = ConnectorId::new(CN_IDX_BB, 0);
+    /// `CN_DST_IDX`/`CN_DST_VAL`.
+    pub const DST: ConnectorId = ConnectorId::new(CN_DST_IDX, CN_DST_VAL);
+    /// Device mapper userspace log, `CN_IDX_DM`/`CN_VAL_DM_USERSPACE_LOG`.
+    pub const DM_USERSPACE_LOG: ConnectorId =
+        ConnectorId::new(CN_IDX_DM, CN_VAL_DM_USERSPACE_LOG);
+    /// DRBD, `CN_IDX_DRBD`/`CN_VAL_DRBD`.
+    pub const DRBD: ConnectorId = ConnectorId::new(CN_IDX_DRBD, CN_VAL_DRBD);
+    /// Hyper-V key value pair daemon, `CN_KVP_IDX`/`CN_KVP_VAL`.
+    pub const KVP: ConnectorId = ConnectorId::new(CN_KVP_IDX, CN_KVP_VAL);
+    /// Hyper-V volume shadow copy daemon, `CN_VSS_IDX`/`CN_VSS_VAL`.
+    pub const VSS: ConnectorId = ConnectorId::new(CN_VSS_IDX, CN_VSS_VAL);
+
+    /// Every well-known id with its kernel name.
+    pub const WELL_KNOWN: [(ConnectorId, &'static str); 10] = [
+        (ConnectorId::PROC, "CN_IDX_PROC"),
+        (ConnectorId::CIFS, "CN_IDX_CIFS"),
+        (ConnectorId::W1, "CN_W1_IDX"),
+        (ConnectorId::V86D_UVESAFB, "CN_IDX_V86D"),
+        (ConnectorId::BB, "CN_IDX_BB"),
+        (ConnectorId::DST, "CN_DST_IDX"),
+        (ConnectorId::DM_USERSPACE_LOG, "CN_IDX_DM"),
+        (ConnectorId::DRBD, "CN_IDX_DRBD"),
+        (ConnectorId::KVP, "CN_KVP_IDX"),
+        (ConnectorId::VSS, "CN_VSS_IDX"),
+    ];
+
+    pub const fn new(idx: u32, value: u32) -> Self {
+        ConnectorId { idx, value }
+    }
+
+    /// The bit selecting this id's multicast group, which is numbered
+    /// by `idx`, in the group mask of a netlink `bind`. `None` for
+    /// groups above 32, which the mask cannot express.
+    ///
+    /// Only needed to build the mask for
+    /// [`ConnectorSocket::bind`](crate::socket::ConnectorSocket::bind);
+    /// [`join`](crate::socket::ConnectorSocket::join) takes any group.
+    pub const fn group_mask(&self) -> Option<u32> {
+        match self.idx {
+            1..=32 => Some(1 << (self.idx - 1)),
+            _ => None,
+        }
+    }
+
+    /// The kernel name of a well-known id, `None` for any other.
+    pub fn name(&self) -> Option<&'static str> {
+        Self::WELL_KNOWN
+            .iter()
+            .find(|(id, _)| id == self)
+            .map(|(_, name)| *name)
+    }
+}
+
+impl From<(u32, u32)> for ConnectorId {
+    fn from((idx, value): (u32, u32)) -> Self {
+        ConnectorId::new(idx, value)
+    }
+}
+
+// Well-known ids are shown by name, others as `idx:value`, both of
+// which `FromStr` accepts back.
+impl fmt::Display for ConnectorId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            Some(name) => f.write_str(name),
+            None => write!(f, "{}:{}", self.idx, self.value),
+        }
+    }
+}
+
+/// Error returned when a string is not a valid [`ConnectorId`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ParseConnectorIdError {
+    input: String,
+}
+
+impl Error for ParseConnectorIdError {}
+
+impl fmt::Display for ParseConnectorIdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid connector id `{}`: expected `idx:val` or a kernel name \
+             such as CN_IDX_PROC",
+            self.input
+        )
+    }
+}
+
+// Parses `idx:val`, with each number in decimal or `0x` hexadecimal,
+// or the kernel name of a well-known id.
+impl FromStr for ConnectorId {
+    type Err = ParseConnectorIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || ParseConnectorIdError {
+            input: s.to_owned(),
+        };
+        let s = s.trim();
+        if let Some((id, _)) = Self::WELL_KNOWN
+            .iter()
+            .find(|(_, name)| name.eq_ignore_ascii_case(s))
+        {
+            return Ok(*id);
+        }
+        let (idx, value) = s.split_once(':').ok_or_else(error)?;
+        let idx = parse_number(idx).ok_or_else(error)?;
+        let value = parse_number(value).ok_or_else(error)?;
+        Ok(ConnectorId::new(idx, value))
+    }
+}
+
+fn parse_number(s: &str) -> Option<u32> {
+    let s = s.trim();
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_from_str_round_trip() {
+        for (id, name) in ConnectorId::WELL_KNOWN {
+            assert_eq!(id.to_string(), name);
+            assert_eq!(name.parse(), Ok(id));
+        }
+        let id = ConnectorId::new(42, 7);
+        assert_eq!(id.to_string(), "42:7");
+        assert_eq!("42:7".parse(), Ok(id));
+    }
+
+    #[test]
+    fn from_str_hex_and_whitespace() {
+        assert_eq!("0x2a:0X7".parse(), Ok(ConnectorId::new(42, 7)));
+        assert_eq!(" 0x1 : 1\n".parse(), Ok(ConnectorId::PROC));
+        assert_eq!("\tcn_idx_proc ".parse(), Ok(ConnectorId::PROC));
+    }
+
+    #[test]
+    fn from_str_rejects_malformed_ids() {
+        for input in ["", "1", "1:", ":1", "0x:1", "1:2:3", "-1:1", "CN_X"] {
+            assert_eq!(
+                input.parse::<ConnectorId>(),
+                Err(ParseConnectorIdError {
+                    input: input.to_owned(),
+                }),
+                "{input:?}",
+            );
+        }
+    }
+}
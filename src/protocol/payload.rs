@@ -0,0 +1,46 @@
+use super::{ConnectorId, DeserializeError, Endianness};
+
+/// The data carried by a connector message.
+///
+/// Implementations describe the payload format of one connector family,
+/// so that [`ConnectorMessage<P>`](super::ConnectorMessage) can hand out
+/// a typed [`payload`](super::ConnectorMessage::payload) instead of raw
+/// bytes. `Vec<u8>` is the untyped payload used by default.
+pub trait ConnectorPayload: Sized {
+    /// The connector this payload is sent on, or `None` if it is not tied
+    /// to one. Parsing a typed payload from a message with another id
+    /// fails with [`DeserializeError::IdMismatch`].
+    const ID: Option<ConnectorId>;
+
+    /// Length of the serialized payload, without padding.
+    fn payload_len(&self) -> usize;
+
+    /// Serialize the payload into `buffer`, which is exactly
+    /// [`Self::payload_len`] bytes long.
+    fn emit_payload(&self, buffer: &mut [u8], endianness: Endianness);
+
+    /// Parse the payload from the `len` bytes of data of a message.
+    fn parse_payload(
+        data: &[u8],
+        endianness: Endianness,
+    ) -> Result<Self, DeserializeError>;
+}
+
+impl ConnectorPayload for Vec<u8> {
+    const ID: Option<ConnectorId> = None;
+
+    fn payload_len(&self) -> usize {
+        self.len()
+    }
+
+    fn emit_payload(&self, buffer: &mut [u8], _endianness: Endianness) {
+        buffer.copy_from_slice(self)
+    }
+
+    fn parse_payload(
+        data: &[u8],
+        _endianness: Endianness,
+    ) -> Result<Self, DeserializeError> {
+        Ok(data.to_vec())
+    }
+}
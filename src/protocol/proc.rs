@@ -0,0 +1,1196 @@
+//! Process events reported by the proc connector, `CN_IDX_PROC`.
+//!
+//! The layouts follow `struct proc_event` in `linux/cn_proc.h`.
+
+mod clock;
+mod control;
+mod exit;
+mod gap;
+mod reorder;
+
+use std::{
+    ops::Range,
+    time::{Duration, SystemTime},
+};
+
+use netlink_packet_core::NetlinkMessage;
+
+use super::{
+    ConnectorId, ConnectorMessage, ConnectorPayload, DeserializeError,
+    Endianness,
+};
+
+pub use self::{clock::*, control::*, exit::*, gap::*, reorder::*};
+
+pub const PROC_EVENT_NONE: u32 = 0x00000000;
+pub const PROC_EVENT_FORK: u32 = 0x00000001;
+pub const PROC_EVENT_EXEC: u32 = 0x00000002;
+pub const PROC_EVENT_UID: u32 = 0x00000004;
+pub const PROC_EVENT_GID: u32 = 0x00000040;
+pub const PROC_EVENT_SID: u32 = 0x00000080;
+pub const PROC_EVENT_PTRACE: u32 = 0x00000100;
+pub const PROC_EVENT_COMM: u32 = 0x00000200;
+/// A filter bit selecting exit events with a non-zero exit code. The
+/// kernel never sends it as `what`; filtered listeners still receive
+/// `PROC_EVENT_EXIT`.
+pub const PROC_EVENT_NONZERO_EXIT: u32 = 0x20000000;
+pub const PROC_EVENT_COREDUMP: u32 = 0x40000000;
+pub const PROC_EVENT_EXIT: u32 = 0x80000000;
+
+/// Length of `comm` in a [`ProcEvent::Comm`] event, `TASK_COMM_LEN`.
+pub const TASK_COMM_LEN: usize = 16;
+
+const WHAT: Range<usize> = 0..4;
+const CPU: Range<usize> = 4..8;
+const TIMESTAMP_NS: Range<usize> = 8..16;
+const EVENT_DATA: usize = 16;
+
+/// Length of the `what`, `cpu` and `timestamp_ns` header.
+pub const PROC_EVENT_HEADER_LEN: usize = EVENT_DATA;
+
+/// Length of `struct proc_event`, whose `event_data` union is sized by
+/// its largest members, the exit and comm events.
+pub const PROC_EVENT_LEN: usize = PROC_EVENT_HEADER_LEN + 24;
+
+/// A decoded `struct proc_event`.
+///
+/// Every variant carries the CPU the event was generated on and its
+/// `CLOCK_MONOTONIC` timestamp, followed by the fields of the matching
+/// `event_data` member.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum ProcEvent {
+    /// `PROC_EVENT_NONE`, the acknowledgement of a control message.
+    None {
+        cpu: u32,
+        timestamp_ns: u64,
+        err: u32,
+    },
+    Fork {
+        cpu: u32,
+        timestamp_ns: u64,
+        parent_pid: i32,
+        parent_tgid: i32,
+        child_pid: i32,
+        child_tgid: i32,
+    },
+    Exec {
+        cpu: u32,
+        timestamp_ns: u64,
+        process_pid: i32,
+        process_tgid: i32,
+    },
+    Uid {
+        cpu: u32,
+        timestamp_ns: u64,
+        process_pid: i32,
+        process_tgid: i32,
+        ruid: u32,
+        euid: u32,
+    },
+    Gid {
+        cpu: u32,
+        timestamp_ns: u64,
+        process_pid: i32,
+        process_tgid: i32,
+        rgid: u32,
+        egid: u32,
+    },
+    Sid {
+        cpu: u32,
+        timestamp_ns: u64,
+        process_pid: i32,
+        process_tgid: i32,
+    },
+    Ptrace {
+        cpu: u32,
+        timestamp_ns: u64,
+        process_pid: i32,
+        process_tgid: i32,
+        tracer_pid: i32,
+        tracer_tgid: i32,
+    },
+    Comm {
+        cpu: u32,
+        timestamp_ns: u64,
+        process_pid: i32,
+        process_tgid: i32,
+        comm: [u8; TASK_COMM_LEN],
+    },
+    Coredump {
+        cpu: u32,
+        timestamp_ns: u64,
+        process_pid: i32,
+        process_tgid: i32,
+        parent_pid: i32,
+        parent_tgid: i32,
+    },
+    Exit {
+        cpu: u32,
+        timestamp_ns: u64,
+        process_pid: i32,
+        process_tgid: i32,
+        exit_code: u32,
+        exit_signal: u32,
+        parent_pid: i32,
+        parent_tgid: i32,
+    },
+    /// An event with `what` set to `PROC_EVENT_NONZERO_EXIT`, in the
+    /// exit layout. The kernel reports every exit as [`ProcEvent::Exit`],
+    /// even to listeners filtering on non-zero exits, so this only comes
+    /// from synthesized frames or unusual input.
+    NonzeroExit {
+        cpu: u32,
+        timestamp_ns: u64,
+        process_pid: i32,
+        process_tgid: i32,
+        exit_code: u32,
+        exit_signal: u32,
+        parent_pid: i32,
+        parent_tgid: i32,
+    },
+    /// An event type this crate does not know, with the raw
+    /// `event_data` bytes.
+    Unknown {
+        what: u32,
+        cpu: u32,
+        timestamp_ns: u64,
+        data: Vec<u8>,
+    },
+}
+
+// Reads the fields of an `event_data` member in order, failing if the
+// data is too short for the member.
+struct EventDataReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+    endianness: Endianness,
+}
+
+impl<'a> EventDataReader<'a> {
+    fn new(
+        data: &'a [u8],
+        len: usize,
+        endianness: Endianness,
+    ) -> Result<Self, DeserializeError> {
+        let needed = EVENT_DATA + len;
+        if data.len() < needed {
+            return Err(DeserializeError::Truncated {
+                needed,
+                got: data.len(),
+            });
+        }
+        Ok(EventDataReader {
+            data,
+            offset: EVENT_DATA,
+            endianness,
+        })
+    }
+
+    fn bytes(&mut self, len: usize) -> &'a [u8] {
+        let bytes = &self.data[self.offset..self.offset + len];
+        self.offset += len;
+        bytes
+    }
+
+    fn u32(&mut self) -> u32 {
+        let endianness = self.endianness;
+        endianness.read_u32(self.bytes(4))
+    }
+
+    fn i32(&mut self) -> i32 {
+        let endianness = self.endianness;
+        endianness.read_i32(self.bytes(4))
+    }
+}
+
+// Writes the fields of an `event_data` member in order.
+struct EventDataWriter<'a> {
+    buffer: &'a mut [u8],
+    offset: usize,
+    endianness: Endianness,
+}
+
+impl EventDataWriter<'_> {
+    fn bytes(&mut self, bytes: &[u8]) -> &mut Self {
+        let end = self.offset + bytes.len();
+        self.buffer[self.offset..end].copy_from_slice(bytes);
+        self.offset = end;
+        self
+    }
+
+    fn u32(&mut self, value: u32) -> &mut Self {
+        let end = self.offset + 4;
+        self.endianness
+            .write_u32(&mut self.buffer[self.offset..end], value);
+        self.offset = end;
+        self
+    }
+
+    fn i32(&mut self, value: i32) -> &mut Self {
+        let end = self.offset + 4;
+        self.endianness
+            .write_i32(&mut self.buffer[self.offset..end], value);
+        self.offset = end;
+        self
+    }
+}
+
+impl ProcEvent {
+    /// Parse a `struct proc_event` from the data of a proc connector
+    /// message.
+    ///
+    /// The data must hold the header and the `event_data` member of the
+    /// event type. Unknown event types are returned as
+    /// [`ProcEvent::Unknown`].
+    pub fn parse(
+        data: &[u8],
+        endianness: Endianness,
+    ) -> Result<Self, DeserializeError> {
+        if data.len() < PROC_EVENT_HEADER_LEN {
+            return Err(DeserializeError::Truncated {
+                needed: PROC_EVENT_HEADER_LEN,
+                got: data.len(),
+            });
+        }
+        let what = endianness.read_u32(&data[WHAT]);
+        let cpu = endianness.read_u32(&data[CPU]);
+        let timestamp_ns = endianness.read_u64(&data[TIMESTAMP_NS]);
+
+        let event = match what {
+            PROC_EVENT_NONE => {
+                let mut r = EventDataReader::new(data, 4, endianness)?;
+                ProcEvent::None {
+                    cpu,
+                    timestamp_ns,
+                    err: r.u32(),
+                }
+            }
+            PROC_EVENT_FORK => {
+                let mut r = EventDataReader::new(data, 16, endianness)?;
+                ProcEvent::Fork {
+                    cpu,
+                    timestamp_ns,
+                    parent_pid: r.i32(),
+                    parent_tgid: r.i32(),
+                    child_pid: r.i32(),
+                    child_tgid: r.i32(),
+                }
+            }
+            PROC_EVENT_EXEC => {
+                let mut r = EventDataReader::new(data, 8, endianness)?;
+                ProcEvent::Exec {
+                    cpu,
+                    timestamp_ns,
+                    process_pid: r.i32(),
+                    process_tgid: r.i32(),
+                }
+            }
+            PROC_EVENT_UID => {
+                let mut r = EventDataReader::new(data, 16, endianness)?;
+                ProcEvent::Uid {
+                    cpu,
+                    timestamp_ns,
+                    process_pid: r.i32(),
+                    process_tgid: r.i32(),
+                    ruid: r.u32(),
+                    euid: r.u32(),
+                }
+            }
+            PROC_EVENT_GID => {
+                let mut r = EventDataReader::new(data, 16, endianness)?;
+                ProcEvent::Gid {
+                    cpu,
+                    timestamp_ns,
+                    process_pid: r.i32(),
+                    process_tgid: r.i32(),
+                    rgid: r.u32(),
+                    egid: r.u32(),
+                }
+            }
+            PROC_EVENT_SID => {
+                let mut r = EventDataReader::new(data, 8, endianness)?;
+                ProcEvent::Sid {
+                    cpu,
+                    timestamp_ns,
+                    process_pid: r.i32(),
+                    process_tgid: r.i32(),
+                }
+            }
+            PROC_EVENT_PTRACE => {
+                let mut r = EventDataReader::new(data, 16, endianness)?;
+                ProcEvent::Ptrace {
+                    cpu,
+                    timestamp_ns,
+                    process_pid: r.i32(),
+                    process_tgid: r.i32(),
+                    tracer_pid: r.i32(),
+                    tracer_tgid: r.i32(),
+                }
+            }
+            PROC_EVENT_COMM => {
+                let mut r =
+                    EventDataReader::new(data, 8 + TASK_COMM_LEN, endianness)?;
+                let process_pid = r.i32();
+                let process_tgid = r.i32();
+                let mut comm = [0; TASK_COMM_LEN];
+                comm.copy_from_slice(r.bytes(TASK_COMM_LEN));
+                ProcEvent::Comm {
+                    cpu,
+                    timestamp_ns,
+                    process_pid,
+                    process_tgid,
+                    comm,
+                }
+            }
+            PROC_EVENT_COREDUMP => {
+                let mut r = EventDataReader::new(data, 16, endianness)?;
+                ProcEvent::Coredump {
+                    cpu,
+                    timestamp_ns,
+                    process_pid: r.i32(),
+                    process_tgid: r.i32(),
+                    parent_pid: r.i32(),
+                    parent_tgid: r.i32(),
+                }
+            }
+            PROC_EVENT_EXIT | PROC_EVENT_NONZERO_EXIT => {
+                let mut r = EventDataReader::new(data, 24, endianness)?;
+                let process_pid = r.i32();
+                let process_tgid = r.i32();
+                let exit_code = r.u32();
+                let exit_signal = r.u32();
+                let parent_pid = r.i32();
+                let parent_tgid = r.i32();
+                if what == PROC_EVENT_EXIT {
+                    ProcEvent::Exit {
+                        cpu,
+                        timestamp_ns,
+                        process_pid,
+                        process_tgid,
+                        exit_code,
+                        exit_signal,
+                        parent_pid,
+                        parent_tgid,
+                    }
+                } else {
+                    ProcEvent::NonzeroExit {
+                        cpu,
+                        timestamp_ns,
+                        process_pid,
+                        process_tgid,
+                        exit_code,
+                        exit_signal,
+                        parent_pid,
+                        parent_tgid,
+                    }
+                }
+            }
+            what => ProcEvent::Unknown {
+                what,
+                cpu,
+                timestamp_ns,
+                data: data[EVENT_DATA..].to_vec(),
+            },
+        };
+        Ok(event)
+    }
+
+    /// Length of the serialized event: the size of `struct proc_event`,
+    /// or the header and raw data of an unknown event.
+    pub fn buffer_len(&self) -> usize {
+        match self {
+            ProcEvent::Unknown { data, .. } => {
+                PROC_EVENT_HEADER_LEN + data.len()
+            }
+            _ => PROC_EVENT_LEN,
+        }
+    }
+
+    /// Serialize the event into `buffer` the way the kernel does, with
+    /// the unused part of `event_data` zeroed.
+    ///
+    /// # Panic
+    ///
+    /// This method panics if `buffer` is not exactly
+    /// [`Self::buffer_len`] bytes long.
+    pub fn emit(&self, buffer: &mut [u8], endianness: Endianness) {
+        assert_eq!(buffer.len(), self.buffer_len());
+        buffer.fill(0);
+        endianness.write_u32(&mut buffer[WHAT], self.what());
+        endianness.write_u32(&mut buffer[CPU], self.cpu());
+        endianness.write_u64(&mut buffer[TIMESTAMP_NS], self.timestamp_ns());
+
+        let mut w = EventDataWriter {
+            buffer,
+            offset: EVENT_DATA,
+            endianness,
+        };
+        match self {
+            ProcEvent::None { err, .. } => {
+                w.u32(*err);
+            }
+            ProcEvent::Fork {
+                parent_pid,
+                parent_tgid,
+                child_pid,
+                child_tgid,
+                ..
+            } => {
+                w.i32(*parent_pid)
+                    .i32(*parent_tgid)
+                    .i32(*child_pid)
+                    .i32(*child_tgid);
+            }
+            ProcEvent::Exec {
+                process_pid,
+                process_tgid,
+                ..
+            }
+            | ProcEvent::Sid {
+                process_pid,
+                process_tgid,
+                ..
+            } => {
+                w.i32(*process_pid).i32(*process_tgid);
+            }
+            ProcEvent::Uid {
+                process_pid,
+                process_tgid,
+                ruid: r,
+                euid: e,
+                ..
+            }
+            | ProcEvent::Gid {
+                process_pid,
+                process_tgid,
+                rgid: r,
+                egid: e,
+                ..
+            } => {
+                w.i32(*process_pid).i32(*process_tgid).u32(*r).u32(*e);
+            }
+            ProcEvent::Ptrace {
+                process_pid,
+                process_tgid,
+                tracer_pid: pid,
+                tracer_tgid: tgid,
+                ..
+            }
+            | ProcEvent::Coredump {
+                process_pid,
+                process_tgid,
+                parent_pid: pid,
+                parent_tgid: tgid,
+                ..
+            } => {
+                w.i32(*process_pid).i32(*process_tgid).i32(*pid).i32(*tgid);
+            }
+            ProcEvent::Comm {
+                process_pid,
+                process_tgid,
+                comm,
+                ..
+            } => {
+                w.i32(*process_pid).i32(*process_tgid).bytes(comm);
+            }
+            ProcEvent::Exit {
+                process_pid,
+                process_tgid,
+                exit_code,
+                exit_signal,
+                parent_pid,
+                parent_tgid,
+                ..
+            }
+            | ProcEvent::NonzeroExit {
+                process_pid,
+                process_tgid,
+                exit_code,
+                exit_signal,
+                parent_pid,
+                parent_tgid,
+                ..
+            } => {
+                w.i32(*process_pid)
+                    .i32(*process_tgid)
+                    .u32(*exit_code)
+                    .u32(*exit_signal)
+                    .i32(*parent_pid)
+                    .i32(*parent_tgid);
+            }
+            ProcEvent::Unknown { data, .. } => {
+                w.bytes(data);
+            }
+        }
+    }
+
+    /// Wrap the event in a netlink message laid out like the ones the
+    /// kernel multicasts: type `NLMSG_DONE`, `seq` in both the netlink
+    /// and the connector header, and `ack` and flags zero.
+    pub fn into_netlink_message(
+        self,
+        seq: u32,
+    ) -> NetlinkMessage<ConnectorMessage<ProcEvent>> {
+        let message = ConnectorMessage::from_payload(self).with_seq(seq);
+        let mut message = NetlinkMessage::from(message);
+        message.header.sequence_number = seq;
+        message.finalize();
+        message
+    }
+
+    /// The `what` field identifying the event type.
+    pub fn what(&self) -> u32 {
+        match self {
+            ProcEvent::None { .. } => PROC_EVENT_NONE,
+            ProcEvent::Fork { .. } => PROC_EVENT_FORK,
+            ProcEvent::Exec { .. } => PROC_EVENT_EXEC,
+            ProcEvent::Uid { .. } => PROC_EVENT_UID,
+            ProcEvent::Gid { .. } => PROC_EVENT_GID,
+            ProcEvent::Sid { .. } => PROC_EVENT_SID,
+            ProcEvent::Ptrace { .. } => PROC_EVENT_PTRACE,
+            ProcEvent::Comm { .. } => PROC_EVENT_COMM,
+            ProcEvent::Coredump { .. } => PROC_EVENT_COREDUMP,
+            ProcEvent::Exit { .. } => PROC_EVENT_EXIT,
+            ProcEvent::NonzeroExit { .. } => PROC_EVENT_NONZERO_EXIT,
+            ProcEvent::Unknown { what, .. } => *what,
+        }
+    }
+
+    /// The CPU the event was generated on.
+    pub fn cpu(&self) -> u32 {
+        match self {
+            ProcEvent::None { cpu, .. }
+            | ProcEvent::Fork { cpu, .. }
+            | ProcEvent::Exec { cpu, .. }
+            | ProcEvent::Uid { cpu, .. }
+            | ProcEvent::Gid { cpu, .. }
+            | ProcEvent::Sid { cpu, .. }
+            | ProcEvent::Ptrace { cpu, .. }
+            | ProcEvent::Comm { cpu, .. }
+            | ProcEvent::Coredump { cpu, .. }
+            | ProcEvent::Exit { cpu, .. }
+            | ProcEvent::NonzeroExit { cpu, .. }
+            | ProcEvent::Unknown { cpu, .. } => *cpu,
+        }
+    }
+
+    /// The `CLOCK_MONOTONIC` time the event was generated at.
+    pub fn timestamp_ns(&self) -> u64 {
+        match self {
+            ProcEvent::None { timestamp_ns, .. }
+            | ProcEvent::Fork { timestamp_ns, .. }
+            | ProcEvent::Exec { timestamp_ns, .. }
+            | ProcEvent::Uid { timestamp_ns, .. }
+            | ProcEvent::Gid { timestamp_ns, .. }
+            | ProcEvent::Sid { timestamp_ns, .. }
+            | ProcEvent::Ptrace { timestamp_ns, .. }
+            | ProcEvent::Comm { timestamp_ns, .. }
+            | ProcEvent::Coredump { timestamp_ns, .. }
+            | ProcEvent::Exit { timestamp_ns, .. }
+            | ProcEvent::NonzeroExit { timestamp_ns, .. }
+            | ProcEvent::Unknown { timestamp_ns, .. } => *timestamp_ns,
+        }
+    }
+
+    /// The event's timestamp as time since boot.
+    ///
+    /// `CLOCK_MONOTONIC` does not advance while the system is
+    /// suspended, so this excludes time spent in suspend.
+    pub fn since_boot(&self) -> Duration {
+        Duration::from_nanos(self.timestamp_ns())
+    }
+
+    /// The wall-clock time of the event, converted with the offset
+    /// sampled by `clock`, see [`ProcClock::system_time`].
+    pub fn system_time<C: ClockSource>(
+        &self,
+        clock: &ProcClock<C>,
+    ) -> Option<SystemTime> {
+        clock.system_time(self.timestamp_ns())
+    }
+
+    /// How the process terminated, for exit events.
+    ///
+    /// This is decoded from `exit_code`; `exit_signal` is the signal
+    /// sent to the parent, usually `SIGCHLD`, not the one that killed
+    /// the process.
+    pub fn exit_status(&self) -> Option<ExitStatus> {
+        match self {
+            ProcEvent::Exit { exit_code, .. }
+            | ProcEvent::NonzeroExit { exit_code, .. } => {
+                Some(ExitStatus::from_raw(*exit_code))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl ConnectorPayload for ProcEvent {
+    const ID: Option<ConnectorId> = Some(ConnectorId::PROC);
+
+    fn payload_len(&self) -> usize {
+        self.buffer_len()
+    }
+
+    fn emit_payload(&self, buffer: &mut [u8], endianness: Endianness) {
+        self.emit(buffer, endianness)
+    }
+
+    fn parse_payload(
+        data: &[u8],
+        endianness: Endianness,
+    ) -> Result<Self, DeserializeError> {
+        ProcEvent::parse(data, endianness)
+    }
+}
+
+#[cfg(all(test, target_endian = "little"))]
+mod tests {
+    use netlink_packet_core::{NetlinkMessage, NetlinkSerializable};
+
+    use super::*;
+    use crate::protocol::{
+        ConnectorMessageBuffer, ConnectorMessageRef, ConnectorMessages,
+        DecodedMessage, DecoderRegistry, ParseMode,
+    };
+
+    // Frames laid out like the ones the kernel multicasts, one per
+    // event type, with pids 1000 for the parent and 1001 for the child.
+
+    // PROC_EVENT_NONE, seq 0, ack 1.
+    #[rustfmt::skip]
+    const ACK: [u8; 76] = [
+        // nlmsghdr: len, type NLMSG_DONE, flags, seq, pid
+        0x4c, 0x00, 0x00, 0x00,
+        0x03, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00,
+        // cn_msg: CN_IDX_PROC, CN_VAL_PROC, seq, ack, len, flags
+        0x01, 0x00, 0x00, 0x00,
+        0x01, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00,
+        0x01, 0x00, 0x00, 0x00,
+        0x28, 0x00, 0x00, 0x00,
+        // proc_event: what, cpu, timestamp_ns
+        0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00,
+        0xd4, 0xc3, 0xb2, 0xa1, 0xf8, 0x01, 0x00, 0x00,
+        // event_data: err
+        0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00,
+    ];
+
+    // PROC_EVENT_FORK, seq 1, ack 0.
+    #[rustfmt::skip]
+    const FORK: [u8; 76] = [
+        // nlmsghdr: len, type NLMSG_DONE, flags, seq, pid
+        0x4c, 0x00, 0x00, 0x00,
+        0x03, 0x00, 0x00, 0x00,
+        0x01, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00,
+        // cn_msg: CN_IDX_PROC, CN_VAL_PROC, seq, ack, len, flags
+        0x01, 0x00, 0x00, 0x00,
+        0x01, 0x00, 0x00, 0x00,
+        0x01, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00,
+        0x28, 0x00, 0x00, 0x00,
+        // proc_event: what, cpu, timestamp_ns
+        0x01, 0x00, 0x00, 0x00,
+        0x01, 0x00, 0x00, 0x00,
+        0xd5, 0xc3, 0xb2, 0xa1, 0xf8, 0x01, 0x00, 0x00,
+        // event_data: parent_pid, parent_tgid, child_pid, child_tgid
+        0xe8, 0x03, 0x00, 0x00,
+        0xe8, 0x03, 0x00, 0x00,
+        0xe9, 0x03, 0x00, 0x00,
+        0xe9, 0x03, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    // PROC_EVENT_EXEC, seq 2, ack 0.
+    #[rustfmt::skip]
+    const EXEC: [u8; 76] = [
+        // nlmsghdr: len, type NLMSG_DONE, flags, seq, pid
+        0x4c, 0x00, 0x00, 0x00,
+        0x03, 0x00, 0x00, 0x00,
+        0x02, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00,
+        // cn_msg: CN_IDX_PROC, CN_VAL_PROC, seq, ack, len, flags
+        0x01, 0x00, 0x00, 0x00,
+        0x01, 0x00, 0x00, 0x00,
+        0x02, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00,
+        0x28, 0x00, 0x00, 0x00,
+        // proc_event: what, cpu, timestamp_ns
+        0x02, 0x00, 0x00, 0x00,
+        0x02, 0x00, 0x00, 0x00,
+        0xd6, 0xc3, 0xb2, 0xa1, 0xf8, 0x01, 0x00, 0x00,
+        // event_data: process_pid, process_tgid
+        0xe9, 0x03, 0x00, 0x00,
+        0xe9, 0x03, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    // PROC_EVENT_UID, seq 3, ack 0.
+    #[rustfmt::skip]
+    const UID: [u8; 76] = [
+        // nlmsghdr: len, type NLMSG_DONE, flags, seq, pid
+        0x4c, 0x00, 0x00, 0x00,
+        0x03, 0x00, 0x00, 0x00,
+        0x03, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00,
+        // cn_msg: CN_IDX_PROC, CN_VAL_PROC, seq, ack, len, flags
+        0x01, 0x00, 0x00, 0x00,
+        0x01, 0x00, 0x00, 0x00,
+        0x03, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00,
+        0x28, 0x00, 0x00, 0x00,
+        // proc_event: what, cpu, timestamp_ns
+        0x04, 0x00, 0x00, 0x00,
+        0x03, 0x00, 0x00, 0x00,
+        0xd7, 0xc3, 0xb2, 0xa1, 0xf8, 0x01, 0x00, 0x00,
+        // event_data: process_pid, process_tgid, ruid, euid
+        0xe9, 0x03, 0x00, 0x00,
+        0xe9, 0x03, 0x00, 0x00,
+        0xe8, 0x03, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    // PROC_EVENT_GID, seq 4, ack 0.
+    #[rustfmt::skip]
+    const GID: [u8; 76] = [
+        // nlmsghdr: len, type NLMSG_DONE, flags, seq, pid
+        0x4c, 0x00, 0x00, 0x00,
+        0x03, 0x00, 0x00, 0x00,
+        0x04, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00,
+        // cn_msg: CN_IDX_PROC, CN_VAL_PROC, seq, ack, len, flags
+        0x01, 0x00, 0x00, 0x00,
+        0x01, 0x00, 0x00, 0x00,
+        0x04, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00,
+        0x28, 0x00, 0x00, 0x00,
+        // proc_event: what, cpu, timestamp_ns
+        0x40, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00,
+        0xd8, 0xc3, 0xb2, 0xa1, 0xf8, 0x01, 0x00, 0x00,
+        // event_data: process_pid, process_tgid, rgid, egid
+        0xe9, 0x03, 0x00, 0x00,
+        0xe9, 0x03, 0x00, 0x00,
+        0xe8, 0x03, 0x00, 0x00,
+        0xe8, 0x03, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    // PROC_EVENT_SID, seq 5, ack 0.
+    #[rustfmt::skip]
+    const SID: [u8; 76] = [
+        // nlmsghdr: len, type NLMSG_DONE, flags, seq, pid
+        0x4c, 0x00, 0x00, 0x00,
+        0x03, 0x00, 0x00, 0x00,
+        0x05, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00,
+        // cn_msg: CN_IDX_PROC, CN_VAL_PROC, seq, ack, len, flags
+        0x01, 0x00, 0x00, 0x00,
+        0x01, 0x00, 0x00, 0x00,
+        0x05, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00,
+        0x28, 0x00, 0x00, 0x00,
+        // proc_event: what, cpu, timestamp_ns
+        0x80, 0x00, 0x00, 0x00,
+        0x01, 0x00, 0x00, 0x00,
+        0xd9, 0xc3, 0xb2, 0xa1, 0xf8, 0x01, 0x00, 0x00,
+        // event_data: process_pid, process_tgid
+        0xe9, 0x03, 0x00, 0x00,
+        0xe9, 0x03, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    // PROC_EVENT_PTRACE, seq 6, ack 0.
+    #[rustfmt::skip]
+    const PTRACE: [u8; 76] = [
+        // nlmsghdr: len, type NLMSG_DONE, flags, seq, pid
+        0x4c, 0x00, 0x00, 0x00,
+        0x03, 0x00, 0x00, 0x00,
+        0x06, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00,
+        // cn_msg: CN_IDX_PROC, CN_VAL_PROC, seq, ack, len, flags
+        0x01, 0x00, 0x00, 0x00,
+        0x01, 0x00, 0x00, 0x00,
+        0x06, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00,
+        0x28, 0x00, 0x00, 0x00,
+        // proc_event: what, cpu, timestamp_ns
+        0x00, 0x01, 0x00, 0x00,
+        0x02, 0x00, 0x00, 0x00,
+        0xda, 0xc3, 0xb2, 0xa1, 0xf8, 0x01, 0x00, 0x00,
+        // event_data: process_pid, process_tgid, tracer_pid, tracer_tgid
+        0xe9, 0x03, 0x00, 0x00,
+        0xe9, 0x03, 0x00, 0x00,
+        0xe8, 0x03, 0x00, 0x00,
+        0xe8, 0x03, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    // PROC_EVENT_COMM, seq 7, ack 0.
+    #[rustfmt::skip]
+    const COMM: [u8; 76] = [
+        // nlmsghdr: len, type NLMSG_DONE, flags, seq, pid
+        0x4c, 0x00, 0x00, 0x00,
+        0x03, 0x00, 0x00, 0x00,
+        0x07, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00,
+        // cn_msg: CN_IDX_PROC, CN_VAL_PROC, seq, ack, len, flags
+        0x01, 0x00, 0x00, 0x00,
+        0x01, 0x00, 0x00, 0x00,
+        0x07, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00,
+        0x28, 0x00, 0x00, 0x00,
+        // proc_event: what, cpu, timestamp_ns
+        0x00, 0x02, 0x00, 0x00,
+        0x03, 0x00, 0x00, 0x00,
+        0xdb, 0xc3, 0xb2, 0xa1, 0xf8, 0x01, 0x00, 0x00,
+        // event_data: process_pid, process_tgid, comm
+        0xe9, 0x03, 0x00, 0x00,
+        0xe9, 0x03, 0x00, 0x00,
+        0x73, 0x6c, 0x65, 0x65, 0x70, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    // PROC_EVENT_COREDUMP, seq 8, ack 0.
+    #[rustfmt::skip]
+    const COREDUMP: [u8; 76] = [
+        // nlmsghdr: len, type NLMSG_DONE, flags, seq, pid
+        0x4c, 0x00, 0x00, 0x00,
+        0x03, 0x00, 0x00, 0x00,
+        0x08, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00,
+        // cn_msg: CN_IDX_PROC, CN_VAL_PROC, seq, ack, len, flags
+        0x01, 0x00, 0x00, 0x00,
+        0x01, 0x00, 0x00, 0x00,
+        0x08, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00,
+        0x28, 0x00, 0x00, 0x00,
+        // proc_event: what, cpu, timestamp_ns
+        0x00, 0x00, 0x00, 0x40,
+        0x00, 0x00, 0x00, 0x00,
+        0xdc, 0xc3, 0xb2, 0xa1, 0xf8, 0x01, 0x00, 0x00,
+        // event_data: process_pid, process_tgid, parent_pid, parent_tgid
+        0xe9, 0x03, 0x00, 0x00,
+        0xe9, 0x03, 0x00, 0x00,
+        0xe8, 0x03, 0x00, 0x00,
+        0xe8, 0x03, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    // PROC_EVENT_EXIT, seq 9, ack 0.
+    #[rustfmt::skip]
+    const EXIT: [u8; 76] = [
+        // nlmsghdr: len, type NLMSG_DONE, flags, seq, pid
+        0x4c, 0x00, 0x00, 0x00,
+        0x03, 0x00, 0x00, 0x00,
+        0x09, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00,
+        // cn_msg: CN_IDX_PROC, CN_VAL_PROC, seq, ack, len, flags
+        0x01, 0x00, 0x00, 0x00,
+        0x01, 0x00, 0x00, 0x00,
+        0x09, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00,
+        0x28, 0x00, 0x00, 0x00,
+        // proc_event: what, cpu, timestamp_ns
+        0x00, 0x00, 0x00, 0x80,
+        0x01, 0x00, 0x00, 0x00,
+        0xdd, 0xc3, 0xb2, 0xa1, 0xf8, 0x01, 0x00, 0x00,
+        // event_data: process_pid, process_tgid, exit_code, exit_signal, parent_pid, parent_tgid
+        0xe9, 0x03, 0x00, 0x00,
+        0xe9, 0x03, 0x00, 0x00,
+        0x00, 0x01, 0x00, 0x00,
+        0x11, 0x00, 0x00, 0x00,
+        0xe8, 0x03, 0x00, 0x00,
+        0xe8, 0x03, 0x00, 0x00,
+    ];
+
+    const BASE_NS: u64 = 0x0000_01f8_a1b2_c3d4;
+
+    // Each fixture with its connector `seq` and `ack` and the event it
+    // holds.
+    fn fixtures() -> Vec<(&'static [u8; 76], u32, u32, ProcEvent)> {
+        let mut comm = [0; TASK_COMM_LEN];
+        comm[..5].copy_from_slice(b"sleep");
+        vec![
+            (
+                &ACK,
+                0,
+                1,
+                ProcEvent::None {
+                    cpu: 0,
+                    timestamp_ns: BASE_NS,
+                    err: 0,
+                },
+            ),
+            (
+                &FORK,
+                1,
+                0,
+                ProcEvent::Fork {
+                    cpu: 1,
+                    timestamp_ns: BASE_NS + 1,
+                    parent_pid: 1000,
+                    parent_tgid: 1000,
+                    child_pid: 1001,
+                    child_tgid: 1001,
+                },
+            ),
+            (
+                &EXEC,
+                2,
+                0,
+                ProcEvent::Exec {
+                    cpu: 2,
+                    timestamp_ns: BASE_NS + 2,
+                    process_pid: 1001,
+                    process_tgid: 1001,
+                },
+            ),
+            (
+                &UID,
+                3,
+                0,
+                ProcEvent::Uid {
+                    cpu: 3,
+                    timestamp_ns: BASE_NS + 3,
+                    process_pid: 1001,
+                    process_tgid: 1001,
+                    ruid: 1000,
+                    euid: 0,
+                },
+            ),
+            (
+                &GID,
+                4,
+                0,
+                ProcEvent::Gid {
+                    cpu: 0,
+                    timestamp_ns: BASE_NS + 4,
+                    process_pid: 1001,
+                    process_tgid: 1001,
+                    rgid: 1000,
+                    egid: 1000,
+                },
+            ),
+            (
+                &SID,
+                5,
+                0,
+                ProcEvent::Sid {
+                    cpu: 1,
+                    timestamp_ns: BASE_NS + 5,
+                    process_pid: 1001,
+                    process_tgid: 1001,
+                },
+            ),
+            (
+                &PTRACE,
+                6,
+                0,
+                ProcEvent::Ptrace {
+                    cpu: 2,
+                    timestamp_ns: BASE_NS + 6,
+                    process_pid: 1001,
+                    process_tgid: 1001,
+                    tracer_pid: 1000,
+                    tracer_tgid: 1000,
+                },
+            ),
+            (
+                &COMM,
+                7,
+                0,
+                ProcEvent::Comm {
+                    cpu: 3,
+                    timestamp_ns: BASE_NS + 7,
+                    process_pid: 1001,
+                    process_tgid: 1001,
+                    comm,
+                },
+            ),
+            (
+                &COREDUMP,
+                8,
+                0,
+                ProcEvent::Coredump {
+                    cpu: 0,
+                    timestamp_ns: BASE_NS + 8,
+                    process_pid: 1001,
+                    process_tgid: 1001,
+                    parent_pid: 1000,
+                    parent_tgid: 1000,
+                },
+            ),
+            (
+                &EXIT,
+                9,
+                0,
+                ProcEvent::Exit {
+                    cpu: 1,
+                    timestamp_ns: BASE_NS + 9,
+                    process_pid: 1001,
+                    process_tgid: 1001,
+                    exit_code: 0x100,
+                    exit_signal: 17,
+                    parent_pid: 1000,
+                    parent_tgid: 1000,
+                },
+            ),
+        ]
+    }
+
+    #[test]
+    fn emit_matches_kernel_frames() {
+        for (bytes, seq, ack, event) in fixtures() {
+            let mut data = [0xff; PROC_EVENT_LEN];
+            event.emit(&mut data, Endianness::Native);
+            assert_eq!(data[..], bytes[36..], "{event:?}");
+
+            let message = ConnectorMessage::from_payload(event.clone())
+                .with_seq(seq)
+                .with_ack(ack);
+            let mut message = NetlinkMessage::from(message);
+            message.header.sequence_number = seq;
+            message.finalize();
+            let mut frame = [0; 76];
+            message.serialize(&mut frame);
+            assert_eq!(&frame, bytes, "{event:?}");
+
+            if ack == 0 {
+                let message = event.clone().into_netlink_message(seq);
+                assert_eq!(message.buffer_len(), 76);
+                message.serialize(&mut frame);
+                assert_eq!(&frame, bytes, "{event:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn parse_kernel_frames() {
+        for (bytes, seq, ack, event) in fixtures() {
+            let message =
+                NetlinkMessage::<ConnectorMessage<ProcEvent>>::deserialize(
+                    bytes,
+                )
+                .unwrap();
+            let message = ConnectorMessage::from_netlink(message).unwrap();
+            assert_eq!(message.id(), ConnectorId::PROC);
+            assert_eq!(message.seq(), seq);
+            assert_eq!(message.ack(), ack);
+            assert_eq!(message.payload(), &event);
+        }
+    }
+
+    // The `cn_msg` of a fixture as captured on a big-endian machine.
+    fn emit_big_endian(seq: u32, ack: u32, event: &ProcEvent) -> Vec<u8> {
+        let message = ConnectorMessage::from_payload(event.clone())
+            .with_seq(seq)
+            .with_ack(ack);
+        let mut bytes = vec![0; message.buffer_len()];
+        message
+            .try_emit_with_endianness(&mut bytes, Endianness::Big)
+            .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn decode_foreign_endian_frames() {
+        for (_, seq, ack, event) in fixtures() {
+            let bytes = emit_big_endian(seq, ack, &event);
+            let buf = ConnectorMessageBuffer::with_endianness(
+                &bytes[..],
+                Endianness::Big,
+            );
+            let message =
+                ConnectorMessageRef::parse_buffer(buf, ParseMode::Strict)
+                    .unwrap();
+            assert_eq!(message.seq(), seq);
+            let decoded = message.decode::<ProcEvent>().unwrap();
+            assert_eq!(decoded.payload(), &event);
+            let decoded = message
+                .to_owned()
+                .decode_with_endianness::<ProcEvent>(Endianness::Big)
+                .unwrap();
+            assert_eq!(decoded.payload(), &event);
+        }
+    }
+
+    #[test]
+    fn registry_decodes_foreign_endian_frames() {
+        let registry = DecoderRegistry::new();
+        for (_, seq, ack, event) in fixtures() {
+            let bytes = emit_big_endian(seq, ack, &event);
+            let buf = ConnectorMessageBuffer::with_endianness(
+                &bytes[..],
+                Endianness::Big,
+            );
+            let message =
+                ConnectorMessageRef::parse_buffer(buf, ParseMode::Strict)
+                    .unwrap();
+            match registry.decode_ref(&message).unwrap() {
+                DecodedMessage::Proc(decoded) => assert_eq!(decoded, event),
+                decoded => panic!("unexpected {decoded:?}"),
+            }
+        }
+    }
+
+    // Owned messages remember the byte order they were parsed with, so
+    // decoding them later does not fall back to the host's.
+    #[test]
+    fn owned_messages_decode_foreign_endian_frames() {
+        let registry = DecoderRegistry::new();
+        for (_, seq, ack, event) in fixtures() {
+            let bytes = emit_big_endian(seq, ack, &event);
+            let buf = ConnectorMessageBuffer::with_endianness(
+                &bytes[..],
+                Endianness::Big,
+            );
+            let parsed =
+                ConnectorMessage::parse_buffer(buf, ParseMode::Strict).unwrap();
+            let batched =
+                ConnectorMessages::with_endianness(&bytes, Endianness::Big)
+                    .next()
+                    .unwrap()
+                    .unwrap();
+            assert_eq!(parsed, batched);
+            assert_eq!(parsed.endianness(), Endianness::Big);
+            assert_eq!(parsed.as_borrowed().endianness(), Endianness::Big);
+
+            let decoded = parsed.decode::<ProcEvent>().unwrap();
+            assert_eq!(decoded.payload(), &event);
+            assert_eq!(decoded.to_raw(), parsed);
+            match registry.decode(&parsed).unwrap() {
+                DecodedMessage::Proc(decoded) => assert_eq!(decoded, event),
+                decoded => panic!("unexpected {decoded:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn control_messages_are_acknowledged_individually() {
+        let listen = ProcControl::listen();
+        let ignore = ProcControl::ignore();
+        let seq = listen.header.sequence_number;
+        assert_ne!(seq, ignore.header.sequence_number);
+
+        let reply = ConnectorMessage::from_payload(ProcEvent::None {
+            cpu: 0,
+            timestamp_ns: BASE_NS,
+            err: 0,
+        })
+        .with_ack(seq.wrapping_add(1));
+        let ack = ProcAck::from_message(&reply).unwrap();
+        assert!(ack.acknowledges_netlink(&listen));
+        assert!(!ack.acknowledges_netlink(&ignore));
+    }
+}
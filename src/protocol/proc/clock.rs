@@ -0,0 +1,289 @@
+use std::{
+    sync::atomic::{AtomicI64, AtomicU64, Ordering},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const NANOS_PER_SEC: u128 = 1_000_000_000;
+
+/// Reads the clocks a [`ProcClock`] relates to each other.
+///
+/// [`SystemClock`] reads the real clocks; tests can provide fixed or
+/// stepping ones instead.
+pub trait ClockSource {
+    /// The current `CLOCK_MONOTONIC` time, the clock of
+    /// `proc_event.timestamp_ns`.
+    fn monotonic(&self) -> Duration;
+
+    /// The current `CLOCK_REALTIME` time.
+    fn realtime(&self) -> SystemTime;
+}
+
+/// The system's `CLOCK_MONOTONIC` and `CLOCK_REALTIME`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl ClockSource for SystemClock {
+    fn monotonic(&self) -> Duration {
+        let mut ts = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+        // SAFETY: `ts` is a valid, writable timespec. CLOCK_MONOTONIC is
+        // always supported, so the call cannot fail.
+        unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+        Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+    }
+
+    fn realtime(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// Converts `CLOCK_MONOTONIC` timestamps of proc events to wall-clock
+/// time.
+///
+/// The clock samples the offset between `CLOCK_REALTIME` and
+/// `CLOCK_MONOTONIC` and adds it to event timestamps, so that
+/// `system_time = timestamp + (realtime - monotonic)`. The offset
+/// changes when the wall clock is set or slewed by NTP, and across
+/// suspend, during which `CLOCK_MONOTONIC` stops. Long-running
+/// listeners should therefore set a resync interval or call
+/// [`ProcClock::resync`] themselves; events are converted with the
+/// offset current at conversion time, not at the time they were
+/// generated.
+#[derive(Debug)]
+pub struct ProcClock<C = SystemClock> {
+    source: C,
+    resync_interval: Option<Duration>,
+    // CLOCK_REALTIME minus CLOCK_MONOTONIC in nanoseconds.
+    offset_ns: AtomicI64,
+    // CLOCK_MONOTONIC in nanoseconds when the offset was sampled.
+    synced_at_ns: AtomicU64,
+}
+
+impl ProcClock {
+    /// Create a clock reading the system clocks, sampling the offset
+    /// once.
+    pub fn new() -> Self {
+        Self::with_source(SystemClock)
+    }
+}
+
+impl Default for ProcClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: ClockSource> ProcClock<C> {
+    /// Create a clock reading `source`, sampling the offset once.
+    pub fn with_source(source: C) -> Self {
+        let clock = ProcClock {
+            source,
+            resync_interval: None,
+            offset_ns: AtomicI64::new(0),
+            synced_at_ns: AtomicU64::new(0),
+        };
+        clock.resync();
+        clock
+    }
+
+    /// Sample the offset again whenever a conversion happens more than
+    /// `interval` after the last sample.
+    pub fn with_resync_interval(mut self, interval: Duration) -> Self {
+        self.resync_interval = Some(interval);
+        self
+    }
+
+    pub fn source(&self) -> &C {
+        &self.source
+    }
+
+    /// Sample the offset between the clocks now.
+    pub fn resync(&self) {
+        // Bracket the realtime reading to halve the error from being
+        // preempted between the two reads.
+        let before = self.source.monotonic();
+        let realtime = self.source.realtime();
+        let after = self.source.monotonic();
+        let monotonic = before + after.saturating_sub(before) / 2;
+
+        let realtime_ns = match realtime.duration_since(UNIX_EPOCH) {
+            Ok(since) => since.as_nanos() as i64,
+            Err(e) => -(e.duration().as_nanos() as i64),
+        };
+        let monotonic_ns = monotonic.as_nanos() as i64;
+        self.offset_ns
+            .store(realtime_ns - monotonic_ns, Ordering::Relaxed);
+        self.synced_at_ns
+            .store(after.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// The sampled `CLOCK_REALTIME - CLOCK_MONOTONIC` offset in
+    /// nanoseconds.
+    pub fn offset_ns(&self) -> i64 {
+        self.offset_ns.load(Ordering::Relaxed)
+    }
+
+    /// Convert a `CLOCK_MONOTONIC` timestamp in nanoseconds to
+    /// wall-clock time, resyncing first if the resync interval has
+    /// passed.
+    ///
+    /// Returns `None` if the result is not representable as a
+    /// [`SystemTime`], which only happens for bogus timestamps.
+    pub fn system_time(&self, timestamp_ns: u64) -> Option<SystemTime> {
+        if let Some(interval) = self.resync_interval {
+            let now = self.source.monotonic().as_nanos() as u64;
+            let synced_at = self.synced_at_ns.load(Ordering::Relaxed);
+            if now.saturating_sub(synced_at) >= interval.as_nanos() as u64 {
+                self.resync();
+            }
+        }
+
+        // The timestamp comes off the wire and may be anything, so add in
+        // a type that cannot overflow.
+        let realtime_ns =
+            i128::from(timestamp_ns) + i128::from(self.offset_ns());
+        let since_epoch = Duration::new(
+            (realtime_ns.unsigned_abs() / NANOS_PER_SEC) as u64,
+            (realtime_ns.unsigned_abs() % NANOS_PER_SEC) as u32,
+        );
+        if realtime_ns >= 0 {
+            UNIX_EPOCH.checked_add(since_epoch)
+        } else {
+            UNIX_EPOCH.checked_sub(since_epoch)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    // Clocks frozen at the given times.
+    struct FixedClock {
+        monotonic: Duration,
+        realtime: SystemTime,
+    }
+
+    impl ClockSource for FixedClock {
+        fn monotonic(&self) -> Duration {
+            self.monotonic
+        }
+
+        fn realtime(&self) -> SystemTime {
+            self.realtime
+        }
+    }
+
+    // Clocks moved by the test, for instance to step the wall clock.
+    struct SteppingClock {
+        monotonic: Cell<Duration>,
+        realtime: Cell<SystemTime>,
+    }
+
+    impl SteppingClock {
+        fn new(monotonic_secs: u64, realtime_secs: u64) -> Self {
+            SteppingClock {
+                monotonic: Cell::new(Duration::from_secs(monotonic_secs)),
+                realtime: Cell::new(
+                    UNIX_EPOCH + Duration::from_secs(realtime_secs),
+                ),
+            }
+        }
+
+        // Let `secs` pass on both clocks, then step the wall clock by
+        // `step_secs`.
+        fn advance(&self, secs: u64, step_secs: u64) {
+            let elapsed = Duration::from_secs(secs);
+            self.monotonic.set(self.monotonic.get() + elapsed);
+            self.realtime.set(
+                self.realtime.get() + elapsed + Duration::from_secs(step_secs),
+            );
+        }
+    }
+
+    impl ClockSource for SteppingClock {
+        fn monotonic(&self) -> Duration {
+            self.monotonic.get()
+        }
+
+        fn realtime(&self) -> SystemTime {
+            self.realtime.get()
+        }
+    }
+
+    fn clock(monotonic_secs: u64, realtime_secs: u64) -> ProcClock<FixedClock> {
+        ProcClock::with_source(FixedClock {
+            monotonic: Duration::from_secs(monotonic_secs),
+            realtime: UNIX_EPOCH + Duration::from_secs(realtime_secs),
+        })
+    }
+
+    #[test]
+    fn system_time_adds_offset() {
+        let clock = clock(100, 1_000_000);
+        assert_eq!(
+            clock.system_time(150_000_000_000),
+            Some(UNIX_EPOCH + Duration::from_secs(1_000_050)),
+        );
+    }
+
+    #[test]
+    fn system_time_does_not_overflow() {
+        let clock = clock(100, 1_000_000);
+        for timestamp_ns in [i64::MAX as u64, i64::MAX as u64 + 1, u64::MAX] {
+            let expected = UNIX_EPOCH
+                + Duration::from_nanos(timestamp_ns)
+                + Duration::from_secs(1_000_000 - 100);
+            assert_eq!(clock.system_time(timestamp_ns), Some(expected));
+        }
+
+        // The wall clock set before the epoch.
+        let clock = ProcClock::with_source(FixedClock {
+            monotonic: Duration::from_secs(100),
+            realtime: UNIX_EPOCH - Duration::from_secs(50),
+        });
+        assert_eq!(
+            clock.system_time(0),
+            Some(UNIX_EPOCH - Duration::from_secs(150)),
+        );
+    }
+
+    #[test]
+    fn resync_interval() {
+        let secs = |secs| Some(UNIX_EPOCH + Duration::from_secs(secs));
+        let clock = ProcClock::with_source(SteppingClock::new(100, 1_000_000))
+            .with_resync_interval(Duration::from_secs(10));
+        assert_eq!(clock.offset_ns(), 999_900_000_000_000);
+
+        // The wall clock is set 5s ahead, the old offset is kept until
+        // the interval has passed.
+        clock.source().advance(5, 5);
+        assert_eq!(clock.system_time(105_000_000_000), secs(1_000_005));
+        clock.source().advance(4, 0);
+        assert_eq!(clock.system_time(109_000_000_000), secs(1_000_009));
+
+        clock.source().advance(1, 0);
+        assert_eq!(clock.system_time(110_000_000_000), secs(1_000_015));
+        assert_eq!(clock.offset_ns(), 999_905_000_000_000);
+    }
+
+    #[test]
+    fn no_resync_without_interval() {
+        let clock = ProcClock::with_source(SteppingClock::new(100, 1_000_000));
+        clock.source().advance(3600, 5);
+        assert_eq!(
+            clock.system_time(3_700_000_000_000),
+            Some(UNIX_EPOCH + Duration::from_secs(1_003_600)),
+        );
+
+        clock.resync();
+        assert_eq!(
+            clock.system_time(3_700_000_000_000),
+            Some(UNIX_EPOCH + Duration::from_secs(1_003_605)),
+        );
+    }
+}
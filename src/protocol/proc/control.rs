@@ -0,0 +1,461 @@
+use std::{
+    io,
+    ops::{BitAnd, BitOr, BitOrAssign},
+};
+
+use netlink_packet_core::{NetlinkMessage, NetlinkPayload};
+
+use super::{
+    ProcEvent, PROC_EVENT_COMM, PROC_EVENT_COREDUMP, PROC_EVENT_EXEC,
+    PROC_EVENT_EXIT, PROC_EVENT_FORK, PROC_EVENT_GID, PROC_EVENT_NONZERO_EXIT,
+    PROC_EVENT_PTRACE, PROC_EVENT_SID, PROC_EVENT_UID,
+};
+use crate::protocol::{
+    ConnectorId, ConnectorMessage, ConnectorPayload, DeserializeError,
+    Endianness, SequenceGenerator,
+};
+
+// Numbers the ready-made control messages, so that their
+// acknowledgements can be told apart.
+static CONTROL_SEQ: SequenceGenerator = SequenceGenerator::new();
+
+pub const PROC_CN_MCAST_LISTEN: u32 = 1;
+pub const PROC_CN_MCAST_IGNORE: u32 = 2;
+
+/// `enum proc_cn_mcast_op`, subscribing to or unsubscribing from proc
+/// events.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum ProcCnMcastOp {
+    Listen,
+    Ignore,
+    Other(u32),
+}
+
+impl From<u32> for ProcCnMcastOp {
+    fn from(op: u32) -> Self {
+        match op {
+            PROC_CN_MCAST_LISTEN => ProcCnMcastOp::Listen,
+            PROC_CN_MCAST_IGNORE => ProcCnMcastOp::Ignore,
+            op => ProcCnMcastOp::Other(op),
+        }
+    }
+}
+
+impl From<ProcCnMcastOp> for u32 {
+    fn from(op: ProcCnMcastOp) -> Self {
+        match op {
+            ProcCnMcastOp::Listen => PROC_CN_MCAST_LISTEN,
+            ProcCnMcastOp::Ignore => PROC_CN_MCAST_IGNORE,
+            ProcCnMcastOp::Other(op) => op,
+        }
+    }
+}
+
+/// Every event type, as set by the kernel for listeners that do not
+/// filter.
+pub const PROC_EVENT_ALL: u32 = PROC_EVENT_FORK
+    | PROC_EVENT_EXEC
+    | PROC_EVENT_UID
+    | PROC_EVENT_GID
+    | PROC_EVENT_SID
+    | PROC_EVENT_PTRACE
+    | PROC_EVENT_COMM
+    | PROC_EVENT_NONZERO_EXIT
+    | PROC_EVENT_COREDUMP
+    | PROC_EVENT_EXIT;
+
+/// The `event_type` of a `struct proc_input`, selecting the events the
+/// kernel delivers to a listener.
+///
+/// Masks combine with `|`. [`ProcEventMask::NONZERO_EXIT`] on its own
+/// only passes exit events with a non-zero exit code.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub struct ProcEventMask(u32);
+
+impl ProcEventMask {
+    pub const FORK: Self = Self(PROC_EVENT_FORK);
+    pub const EXEC: Self = Self(PROC_EVENT_EXEC);
+    pub const UID: Self = Self(PROC_EVENT_UID);
+    pub const GID: Self = Self(PROC_EVENT_GID);
+    pub const SID: Self = Self(PROC_EVENT_SID);
+    pub const PTRACE: Self = Self(PROC_EVENT_PTRACE);
+    pub const COMM: Self = Self(PROC_EVENT_COMM);
+    pub const NONZERO_EXIT: Self = Self(PROC_EVENT_NONZERO_EXIT);
+    pub const COREDUMP: Self = Self(PROC_EVENT_COREDUMP);
+    pub const EXIT: Self = Self(PROC_EVENT_EXIT);
+    pub const ALL: Self = Self(PROC_EVENT_ALL);
+
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// The mask with the given bits, including ones this crate does not
+    /// know about.
+    pub const fn from_bits(bits: u32) -> Self {
+        Self(bits)
+    }
+
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl From<u32> for ProcEventMask {
+    fn from(bits: u32) -> Self {
+        Self(bits)
+    }
+}
+
+impl From<ProcEventMask> for u32 {
+    fn from(mask: ProcEventMask) -> Self {
+        mask.0
+    }
+}
+
+impl BitOr for ProcEventMask {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for ProcEventMask {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl BitAnd for ProcEventMask {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self {
+        Self(self.0 & rhs.0)
+    }
+}
+
+/// A control message sent to the proc connector to start or stop the
+/// delivery of proc events to the sending socket.
+///
+/// Without `events` the message is the legacy bare `proc_cn_mcast_op`
+/// understood by every kernel. With them it is a `struct proc_input`,
+/// which kernels before 6.6 silently drop without an acknowledgement.
+///
+/// The kernel answers with a [`ProcEvent::None`] event, see
+/// [`ProcAck`]. The answer passes through the listener's filter like
+/// any event, so it is only delivered for [`ProcEventMask::ALL`].
+/// [`ProcFilterProbe`] uses this to detect filtering support and fall
+/// back to [`Self::to_legacy`] on older kernels.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct ProcControl {
+    pub op: ProcCnMcastOp,
+    pub events: Option<ProcEventMask>,
+}
+
+impl ProcControl {
+    pub fn new(op: ProcCnMcastOp) -> Self {
+        ProcControl { op, events: None }
+    }
+
+    /// A `struct proc_input` control message restricted to `events`.
+    pub fn filtered(op: ProcCnMcastOp, events: ProcEventMask) -> Self {
+        ProcControl {
+            op,
+            events: Some(events),
+        }
+    }
+
+    /// The same operation in the legacy form, without a filter.
+    pub fn to_legacy(&self) -> Self {
+        Self::new(self.op)
+    }
+
+    /// A ready-to-send `PROC_CN_MCAST_LISTEN` message, numbered from a
+    /// process-wide sequence, see [`Self::to_netlink_message`].
+    pub fn listen() -> NetlinkMessage<ConnectorMessage> {
+        Self::new(ProcCnMcastOp::Listen)
+            .to_netlink_message(CONTROL_SEQ.next_seq())
+    }
+
+    /// A ready-to-send `PROC_CN_MCAST_LISTEN` message asking the kernel
+    /// to only deliver `events`. Unless `events` is
+    /// [`ProcEventMask::ALL`], the kernel sends no acknowledgement.
+    pub fn listen_filtered(
+        events: ProcEventMask,
+    ) -> NetlinkMessage<ConnectorMessage> {
+        Self::filtered(ProcCnMcastOp::Listen, events)
+            .to_netlink_message(CONTROL_SEQ.next_seq())
+    }
+
+    /// A ready-to-send `PROC_CN_MCAST_IGNORE` message, numbered like
+    /// [`Self::listen`].
+    pub fn ignore() -> NetlinkMessage<ConnectorMessage> {
+        Self::new(ProcCnMcastOp::Ignore)
+            .to_netlink_message(CONTROL_SEQ.next_seq())
+    }
+
+    /// Wrap the control message in a netlink message, using `seq` in
+    /// the netlink header and as both the `seq` and `ack` of the
+    /// connector header.
+    ///
+    /// The kernel replaces the `seq` of its acknowledgement but answers
+    /// with `ack + 1`, so the [`ProcAck`] for this message has an `ack`
+    /// of `seq + 1`.
+    pub fn to_netlink_message(
+        &self,
+        seq: u32,
+    ) -> NetlinkMessage<ConnectorMessage> {
+        let message = ConnectorMessage::from_payload(*self)
+            .with_seq(seq)
+            .with_ack(seq);
+        let mut message = NetlinkMessage::from(message.to_raw());
+        message.header.sequence_number = seq;
+        message.finalize();
+        message
+    }
+}
+
+impl ConnectorPayload for ProcControl {
+    const ID: Option<ConnectorId> = Some(ConnectorId::PROC);
+
+    fn payload_len(&self) -> usize {
+        match self.events {
+            Some(_) => 8,
+            None => 4,
+        }
+    }
+
+    fn emit_payload(&self, buffer: &mut [u8], endianness: Endianness) {
+        endianness.write_u32(&mut buffer[..4], self.op.into());
+        if let Some(events) = self.events {
+            endianness.write_u32(&mut buffer[4..8], events.bits());
+        }
+    }
+
+    // The kernel tells both forms apart by their length alone, and
+    // drops messages of any other length.
+    fn parse_payload(
+        data: &[u8],
+        endianness: Endianness,
+    ) -> Result<Self, DeserializeError> {
+        match data.len() {
+            4 => Ok(ProcControl::new(endianness.read_u32(data).into())),
+            8 => {
+                let op = endianness.read_u32(&data[..4]).into();
+                let events = endianness.read_u32(&data[4..8]);
+                Ok(ProcControl::filtered(op, events.into()))
+            }
+            len => Err(DeserializeError::InvalidLength { len }),
+        }
+    }
+}
+
+/// A filtered `PROC_CN_MCAST_LISTEN` for kernels that may not support
+/// filtering, with the messages to detect it and to fall back.
+///
+/// Send [`Self::probe`] first, a filtered listen for
+/// [`ProcEventMask::ALL`], the only mask the kernel acknowledges. A
+/// [`ProcAck`] for which [`Self::is_probe_ack`] holds proves that the
+/// kernel understands filters, so [`Self::filtered`] can narrow the
+/// subscription to the wanted events. Kernels before 6.6 drop the probe
+/// without an answer; if none arrives within a timeout chosen by the
+/// caller, send [`Self::legacy`] and filter in userspace instead.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ProcFilterProbe {
+    probe: NetlinkMessage<ConnectorMessage>,
+    filtered: NetlinkMessage<ConnectorMessage>,
+    legacy: NetlinkMessage<ConnectorMessage>,
+}
+
+impl ProcFilterProbe {
+    /// Build the messages for a listener interested in `events`,
+    /// numbered like [`ProcControl::listen`].
+    pub fn new(events: ProcEventMask) -> Self {
+        let filtered = ProcControl::filtered(ProcCnMcastOp::Listen, events);
+        ProcFilterProbe {
+            probe: ProcControl::listen_filtered(ProcEventMask::ALL),
+            filtered: filtered.to_netlink_message(CONTROL_SEQ.next_seq()),
+            legacy: ProcControl::listen(),
+        }
+    }
+
+    /// The message to send first.
+    pub fn probe(&self) -> &NetlinkMessage<ConnectorMessage> {
+        &self.probe
+    }
+
+    /// Whether `ack` answers [`Self::probe`]. The kernel then supports
+    /// filtering, though [`ProcAck::error`] may still report that the
+    /// subscription failed, for instance for lack of `CAP_NET_ADMIN`.
+    pub fn is_probe_ack(&self, ack: &ProcAck) -> bool {
+        ack.acknowledges_netlink(&self.probe)
+    }
+
+    /// The message to send once the probe is acknowledged, restricting
+    /// the subscription to the wanted events. The kernel does not
+    /// acknowledge it unless they are [`ProcEventMask::ALL`].
+    pub fn filtered(&self) -> &NetlinkMessage<ConnectorMessage> {
+        &self.filtered
+    }
+
+    /// The message to send if the probe is not acknowledged, listening
+    /// to every event.
+    pub fn legacy(&self) -> &NetlinkMessage<ConnectorMessage> {
+        &self.legacy
+    }
+}
+
+/// The kernel's answer to a [`ProcControl`] message, a
+/// `PROC_EVENT_NONE` event carrying the outcome in `err`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct ProcAck {
+    /// Zero on success, otherwise an errno value such as `EPERM` when
+    /// the sender lacks `CAP_NET_ADMIN`.
+    pub err: u32,
+    /// The `ack` of the control message plus one.
+    pub ack: u32,
+}
+
+impl ProcAck {
+    /// Extract the acknowledgement from a proc connector message,
+    /// `None` if it carries another event.
+    pub fn from_message(message: &ConnectorMessage<ProcEvent>) -> Option<Self> {
+        match message.payload() {
+            ProcEvent::None { err, .. } => Some(ProcAck {
+                err: *err,
+                ack: message.ack(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Like [`Self::from_message`], for an untyped message. Messages for
+    /// other connectors fail with [`DeserializeError::IdMismatch`].
+    pub fn parse(
+        message: &ConnectorMessage,
+    ) -> Result<Option<Self>, DeserializeError> {
+        Ok(Self::from_message(&message.decode()?))
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.err == 0
+    }
+
+    /// The error reported by the kernel, if any.
+    pub fn error(&self) -> Option<io::Error> {
+        (self.err != 0).then(|| io::Error::from_raw_os_error(self.err as i32))
+    }
+
+    /// Whether this acknowledges `request`. The kernel replaces the
+    /// `seq` of the acknowledgement, so only `ack` can be matched.
+    pub fn acknowledges<P: ConnectorPayload>(
+        &self,
+        request: &ConnectorMessage<P>,
+    ) -> bool {
+        self.ack == request.ack().wrapping_add(1)
+    }
+
+    /// Like [`Self::acknowledges`], for a request as built by
+    /// [`ProcControl::to_netlink_message`].
+    pub fn acknowledges_netlink(
+        &self,
+        request: &NetlinkMessage<ConnectorMessage>,
+    ) -> bool {
+        match &request.payload {
+            NetlinkPayload::InnerMessage(message) => self.acknowledges(message),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_decode_round_trip() {
+        let filtered = ProcControl::filtered(
+            ProcCnMcastOp::Listen,
+            ProcEventMask::FORK | ProcEventMask::EXIT,
+        );
+        for (control, len) in [(filtered, 8), (filtered.to_legacy(), 4)] {
+            let message = ConnectorMessage::from_payload(control).to_raw();
+            assert_eq!(message.id(), ConnectorId::PROC);
+            assert_eq!(message.data().len(), len);
+            let decoded = message.decode::<ProcControl>().unwrap();
+            assert_eq!(decoded.payload(), &control);
+
+            for endianness in [Endianness::Little, Endianness::Big] {
+                let mut data = vec![0; len];
+                control.emit_payload(&mut data, endianness);
+                assert_eq!(
+                    ProcControl::parse_payload(&data, endianness),
+                    Ok(control),
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn parse_rejects_other_lengths() {
+        for len in [0, 3, 5, 7, 9, 12] {
+            assert_eq!(
+                ProcControl::parse_payload(&vec![0; len], Endianness::Native),
+                Err(DeserializeError::InvalidLength { len }),
+            );
+        }
+    }
+
+    // The kernel answers a control message with `ack + 1`, see
+    // `ProcControl::to_netlink_message`.
+    fn ack_for(request: &NetlinkMessage<ConnectorMessage>) -> ProcAck {
+        match &request.payload {
+            NetlinkPayload::InnerMessage(message) => ProcAck {
+                err: 0,
+                ack: message.ack().wrapping_add(1),
+            },
+            payload => panic!("unexpected {payload:?}"),
+        }
+    }
+
+    fn control(request: &NetlinkMessage<ConnectorMessage>) -> ProcControl {
+        match &request.payload {
+            NetlinkPayload::InnerMessage(message) => {
+                message.decode::<ProcControl>().unwrap().into_payload()
+            }
+            payload => panic!("unexpected {payload:?}"),
+        }
+    }
+
+    #[test]
+    fn filter_probe() {
+        let events = ProcEventMask::NONZERO_EXIT;
+        let probe = ProcFilterProbe::new(events);
+        assert_eq!(
+            control(probe.probe()),
+            ProcControl::filtered(ProcCnMcastOp::Listen, ProcEventMask::ALL),
+        );
+        assert_eq!(
+            control(probe.filtered()),
+            ProcControl::filtered(ProcCnMcastOp::Listen, events),
+        );
+        assert_eq!(
+            control(probe.legacy()),
+            ProcControl::new(ProcCnMcastOp::Listen),
+        );
+
+        assert!(probe.is_probe_ack(&ack_for(probe.probe())));
+        assert!(!probe.is_probe_ack(&ack_for(probe.filtered())));
+        assert!(!probe.is_probe_ack(&ack_for(probe.legacy())));
+        let other = ProcFilterProbe::new(events);
+        assert!(!probe.is_probe_ack(&ack_for(other.probe())));
+    }
+}
@@ -0,0 +1,232 @@
+use std::fmt;
+
+/// How a process terminated, decoded from the wait status in the
+/// `exit_code` of an exit event.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum ExitStatus {
+    /// The process called `exit` with this code.
+    Exited(i32),
+    /// The process was killed by a signal.
+    Signaled { signal: i32, core_dumped: bool },
+    /// The process was stopped by a signal.
+    Stopped(i32),
+}
+
+impl ExitStatus {
+    /// Decode a wait status as returned by `waitpid`.
+    pub fn from_raw(status: u32) -> Self {
+        let status = status as i32;
+        let signal = status & 0x7f;
+        if signal == 0 {
+            ExitStatus::Exited((status >> 8) & 0xff)
+        } else if status & 0xff == 0x7f {
+            ExitStatus::Stopped((status >> 8) & 0xff)
+        } else {
+            ExitStatus::Signaled {
+                signal,
+                core_dumped: status & 0x80 != 0,
+            }
+        }
+    }
+
+    /// Encode the status back into a wait status.
+    pub fn into_raw(self) -> u32 {
+        let status = match self {
+            ExitStatus::Exited(code) => (code & 0xff) << 8,
+            ExitStatus::Signaled {
+                signal,
+                core_dumped,
+            } => (signal & 0x7f) | if core_dumped { 0x80 } else { 0 },
+            ExitStatus::Stopped(signal) => ((signal & 0xff) << 8) | 0x7f,
+        };
+        status as u32
+    }
+
+    /// Whether the process exited with code zero.
+    pub fn success(&self) -> bool {
+        *self == ExitStatus::Exited(0)
+    }
+
+    /// The exit code, if the process exited normally.
+    pub fn code(&self) -> Option<i32> {
+        match self {
+            ExitStatus::Exited(code) => Some(*code),
+            _ => None,
+        }
+    }
+
+    /// The signal that killed or stopped the process.
+    pub fn signal(&self) -> Option<i32> {
+        match self {
+            ExitStatus::Exited(_) => None,
+            ExitStatus::Signaled { signal, .. }
+            | ExitStatus::Stopped(signal) => Some(*signal),
+        }
+    }
+}
+
+impl fmt::Display for ExitStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExitStatus::Exited(code) => write!(f, "exited with code {code}"),
+            ExitStatus::Signaled {
+                signal,
+                core_dumped,
+            } => {
+                write!(f, "killed by {}", SignalName(*signal))?;
+                if *core_dumped {
+                    write!(f, " (core dumped)")?;
+                }
+                Ok(())
+            }
+            ExitStatus::Stopped(signal) => {
+                write!(f, "stopped by {}", SignalName(*signal))
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+impl From<ExitStatus> for std::process::ExitStatus {
+    fn from(status: ExitStatus) -> Self {
+        use std::os::unix::process::ExitStatusExt;
+
+        std::process::ExitStatus::from_raw(status.into_raw() as i32)
+    }
+}
+
+// Signal numbers as used by the kernel on most architectures; alpha,
+// mips, parisc and sparc number some of them differently.
+const SIGNAL_NAMES: [&str; 31] = [
+    "SIGHUP",
+    "SIGINT",
+    "SIGQUIT",
+    "SIGILL",
+    "SIGTRAP",
+    "SIGABRT",
+    "SIGBUS",
+    "SIGFPE",
+    "SIGKILL",
+    "SIGUSR1",
+    "SIGSEGV",
+    "SIGUSR2",
+    "SIGPIPE",
+    "SIGALRM",
+    "SIGTERM",
+    "SIGSTKFLT",
+    "SIGCHLD",
+    "SIGCONT",
+    "SIGSTOP",
+    "SIGTSTP",
+    "SIGTTIN",
+    "SIGTTOU",
+    "SIGURG",
+    "SIGXCPU",
+    "SIGXFSZ",
+    "SIGVTALRM",
+    "SIGPROF",
+    "SIGWINCH",
+    "SIGIO",
+    "SIGPWR",
+    "SIGSYS",
+];
+
+const SIGRTMIN: i32 = 32;
+const SIGRTMAX: i32 = 64;
+
+// Formats a signal number by name, falling back to the number.
+struct SignalName(i32);
+
+impl fmt::Display for SignalName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            signal @ 1..=31 => f.write_str(SIGNAL_NAMES[signal as usize - 1]),
+            SIGRTMIN => f.write_str("SIGRTMIN"),
+            signal @ SIGRTMIN..=SIGRTMAX => {
+                write!(f, "SIGRTMIN+{}", signal - SIGRTMIN)
+            }
+            signal => write!(f, "signal {signal}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cases() -> [(u32, ExitStatus, &'static str); 6] {
+        [
+            (0x0100, ExitStatus::Exited(1), "exited with code 1"),
+            (
+                0x0009,
+                ExitStatus::Signaled {
+                    signal: 9,
+                    core_dumped: false,
+                },
+                "killed by SIGKILL",
+            ),
+            (
+                0x008b,
+                ExitStatus::Signaled {
+                    signal: 11,
+                    core_dumped: true,
+                },
+                "killed by SIGSEGV (core dumped)",
+            ),
+            (0x147f, ExitStatus::Stopped(20), "stopped by SIGTSTP"),
+            (
+                0x0020,
+                ExitStatus::Signaled {
+                    signal: 32,
+                    core_dumped: false,
+                },
+                "killed by SIGRTMIN",
+            ),
+            (
+                0x0022,
+                ExitStatus::Signaled {
+                    signal: 34,
+                    core_dumped: false,
+                },
+                "killed by SIGRTMIN+2",
+            ),
+        ]
+    }
+
+    #[test]
+    fn raw_round_trip() {
+        for (raw, status, display) in cases() {
+            assert_eq!(ExitStatus::from_raw(raw), status, "{raw:#x}");
+            assert_eq!(status.into_raw(), raw, "{status:?}");
+            assert_eq!(status.to_string(), display);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn into_std_exit_status() {
+        use std::os::unix::process::ExitStatusExt;
+
+        for (_, status, _) in cases() {
+            let std_status = std::process::ExitStatus::from(status);
+            assert_eq!(std_status.success(), status.success());
+            assert_eq!(std_status.code(), status.code());
+            match status {
+                ExitStatus::Exited(_) => {
+                    assert_eq!(std_status.signal(), None);
+                    assert_eq!(std_status.stopped_signal(), None);
+                }
+                ExitStatus::Signaled {
+                    signal,
+                    core_dumped,
+                } => {
+                    assert_eq!(std_status.signal(), Some(signal));
+                    assert_eq!(std_status.core_dumped(), core_dumped);
+                }
+                ExitStatus::Stopped(signal) => {
+                    assert_eq!(std_status.stopped_signal(), Some(signal));
+                }
+            }
+        }
+    }
+}
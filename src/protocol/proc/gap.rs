@@ -0,0 +1,217 @@
+use std::{collections::HashMap, io};
+
+use super::ProcEvent;
+use crate::protocol::ConnectorMessage;
+
+/// Events lost between the kernel and a proc connector listener.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Loss {
+    /// Sequence numbers from `cpu` were skipped: `missed` events
+    /// between the `expected` one and the one received.
+    Gap {
+        cpu: u32,
+        expected: u32,
+        got: u32,
+        missed: u32,
+    },
+    /// The socket's receive buffer overflowed, `ENOBUFS`. An unknown
+    /// number of events was dropped.
+    Overflow,
+}
+
+/// Counters of a [`GapDetector`].
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub struct LossStats {
+    /// Number of [`Loss::Gap`] reported.
+    pub gaps: u64,
+    /// Sum of `missed` over all gaps.
+    pub missed: u64,
+    /// Number of [`Loss::Overflow`] reported.
+    pub overflows: u64,
+}
+
+/// Detects lost proc events from the connector sequence numbers.
+///
+/// The kernel numbers proc events with a counter per CPU, stored in
+/// `cn_msg.seq`, while the CPU is reported in the event itself. Any
+/// skipped number means events were dropped, usually because the
+/// socket's receive buffer was full, which `recv` also reports as
+/// `ENOBUFS`. After a loss the view built from the events may be
+/// incomplete and should be resynchronised, for instance from
+/// `/proc`.
+///
+/// Every event sent to the proc connector group counts, so all
+/// messages, including acknowledgements, should be observed. Listeners
+/// that filter with a [`ProcEventMask`](super::ProcEventMask) see gaps
+/// for the events filtered out by the kernel.
+#[derive(Debug, Clone, Default)]
+pub struct GapDetector {
+    expected: HashMap<u32, u32>,
+    stats: LossStats,
+}
+
+impl GapDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record an event with sequence number `seq` from `cpu`, returning
+    /// the gap before it, if any.
+    ///
+    /// The first event from a CPU only sets the expectation. A number
+    /// lower than expected is taken as a restart of the counter rather
+    /// than a loss.
+    pub fn observe(&mut self, cpu: u32, seq: u32) -> Option<Loss> {
+        let expected = self.expected.insert(cpu, seq.wrapping_add(1))?;
+        let missed = seq.wrapping_sub(expected);
+        if missed == 0 || missed > u32::MAX / 2 {
+            return None;
+        }
+        self.stats.gaps += 1;
+        self.stats.missed += u64::from(missed);
+        Some(Loss::Gap {
+            cpu,
+            expected,
+            got: seq,
+            missed,
+        })
+    }
+
+    /// Like [`Self::observe`], taking the CPU from the event and the
+    /// sequence number from the connector header.
+    pub fn observe_message(
+        &mut self,
+        message: &ConnectorMessage<ProcEvent>,
+    ) -> Option<Loss> {
+        self.observe(message.payload().cpu(), message.seq())
+    }
+
+    /// Record a receive error, returning [`Loss::Overflow`] if it is
+    /// `ENOBUFS`. Other errors are not a loss signal.
+    pub fn observe_error(&mut self, error: &io::Error) -> Option<Loss> {
+        self.observe_overflow(error.raw_os_error() == Some(libc::ENOBUFS))
+    }
+
+    /// Like [`Self::observe_error`], for errors already classified as
+    /// an overflow or not, such as the `SocketError::is_overflow` of a
+    /// failed socket receive.
+    pub fn observe_overflow(&mut self, overflow: bool) -> Option<Loss> {
+        if !overflow {
+            return None;
+        }
+        self.stats.overflows += 1;
+        Some(Loss::Overflow)
+    }
+
+    /// The sequence number expected next from `cpu`, if an event from it
+    /// was observed.
+    pub fn expected(&self, cpu: u32) -> Option<u32> {
+        self.expected.get(&cpu).copied()
+    }
+
+    /// Forget the expected sequence numbers, for instance after a
+    /// resync, keeping the statistics.
+    pub fn reset(&mut self) {
+        self.expected.clear();
+    }
+
+    pub fn stats(&self) -> LossStats {
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_event_per_cpu() {
+        let mut detector = GapDetector::new();
+        assert_eq!(detector.expected(0), None);
+        assert_eq!(detector.observe(0, 10), None);
+        assert_eq!(detector.observe(1, 500), None);
+        assert_eq!(detector.expected(0), Some(11));
+        assert_eq!(detector.expected(1), Some(501));
+        assert_eq!(detector.stats(), LossStats::default());
+    }
+
+    #[test]
+    fn gap() {
+        let mut detector = GapDetector::new();
+        detector.observe(2, 10);
+        assert_eq!(detector.observe(2, 11), None);
+        assert_eq!(
+            detector.observe(2, 15),
+            Some(Loss::Gap {
+                cpu: 2,
+                expected: 12,
+                got: 15,
+                missed: 3,
+            }),
+        );
+        assert_eq!(detector.observe(2, 16), None);
+        assert_eq!(
+            detector.stats(),
+            LossStats {
+                gaps: 1,
+                missed: 3,
+                overflows: 0,
+            },
+        );
+    }
+
+    #[test]
+    fn wraparound_is_not_a_loss() {
+        let mut detector = GapDetector::new();
+        detector.observe(0, u32::MAX - 1);
+        assert_eq!(detector.observe(0, u32::MAX), None);
+        assert_eq!(detector.observe(0, 0), None);
+        assert_eq!(detector.expected(0), Some(1));
+
+        // a gap across the wrap is still counted.
+        detector.observe(1, u32::MAX);
+        assert_eq!(
+            detector.observe(1, 1),
+            Some(Loss::Gap {
+                cpu: 1,
+                expected: 0,
+                got: 1,
+                missed: 1,
+            }),
+        );
+    }
+
+    #[test]
+    fn counter_restart() {
+        let mut detector = GapDetector::new();
+        detector.observe(0, 1000);
+        assert_eq!(detector.observe(0, 3), None);
+        assert_eq!(detector.expected(0), Some(4));
+        assert_eq!(detector.stats(), LossStats::default());
+    }
+
+    #[test]
+    fn observe_error() {
+        let mut detector = GapDetector::new();
+        let overflow = io::Error::from_raw_os_error(libc::ENOBUFS);
+        assert_eq!(detector.observe_error(&overflow), Some(Loss::Overflow));
+        let other = io::Error::from_raw_os_error(libc::EINTR);
+        assert_eq!(detector.observe_error(&other), None);
+        assert_eq!(
+            detector.stats(),
+            LossStats {
+                gaps: 0,
+                missed: 0,
+                overflows: 1,
+            },
+        );
+    }
+
+    #[test]
+    fn observe_overflow() {
+        let mut detector = GapDetector::new();
+        assert_eq!(detector.observe_overflow(true), Some(Loss::Overflow));
+        assert_eq!(detector.observe_overflow(false), None);
+        assert_eq!(detector.stats().overflows, 1);
+    }
+}
@@ -0,0 +1,251 @@
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::BinaryHeap,
+    time::Duration,
+};
+
+use super::ProcEvent;
+
+/// Where an event pushed into a [`ProcEventReorder`] fell relative to
+/// the events before it.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum ReorderOutcome {
+    /// No earlier event had a later timestamp.
+    InOrder,
+    /// The event arrived after events with later timestamps and was
+    /// held back to be released before them.
+    Reordered,
+    /// An event with a later timestamp was already released, so the
+    /// event arrived too late for the window and is released out of
+    /// order.
+    Late,
+}
+
+/// Counters of a [`ProcEventReorder`], one per [`ReorderOutcome`].
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub struct ReorderStats {
+    pub in_order: u64,
+    pub reordered: u64,
+    pub late: u64,
+}
+
+/// Buffers proc events and releases them in `timestamp_ns` order.
+///
+/// The kernel sends proc events from the CPU they happen on, so events
+/// from different CPUs can reach the socket out of order. An event is
+/// held until an event at least `window` newer has been pushed, or
+/// until [`Self::advance_to`] moves time past it, which gives events
+/// from other CPUs `window` to catch up. Events with equal timestamps
+/// keep their arrival order.
+#[derive(Debug)]
+pub struct ProcEventReorder {
+    window_ns: u64,
+    pending: BinaryHeap<Reverse<Pending>>,
+    arrivals: u64,
+    newest_ns: u64,
+    released_ns: Option<u64>,
+    stats: ReorderStats,
+}
+
+// An event ordered by timestamp, then arrival.
+#[derive(Debug)]
+struct Pending {
+    timestamp_ns: u64,
+    arrival: u64,
+    event: ProcEvent,
+}
+
+impl PartialEq for Pending {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Pending {}
+
+impl PartialOrd for Pending {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Pending {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.timestamp_ns, self.arrival)
+            .cmp(&(other.timestamp_ns, other.arrival))
+    }
+}
+
+impl ProcEventReorder {
+    pub fn new(window: Duration) -> Self {
+        ProcEventReorder {
+            window_ns: window.as_nanos() as u64,
+            pending: BinaryHeap::new(),
+            arrivals: 0,
+            newest_ns: 0,
+            released_ns: None,
+            stats: ReorderStats::default(),
+        }
+    }
+
+    pub fn window(&self) -> Duration {
+        Duration::from_nanos(self.window_ns)
+    }
+
+    /// Buffer `event`, reporting whether it arrived in order.
+    pub fn push(&mut self, event: ProcEvent) -> ReorderOutcome {
+        let timestamp_ns = event.timestamp_ns();
+        let outcome = if self.released_ns.is_some_and(|ns| timestamp_ns < ns) {
+            self.stats.late += 1;
+            ReorderOutcome::Late
+        } else if timestamp_ns < self.newest_ns {
+            self.stats.reordered += 1;
+            ReorderOutcome::Reordered
+        } else {
+            self.stats.in_order += 1;
+            ReorderOutcome::InOrder
+        };
+
+        self.newest_ns = self.newest_ns.max(timestamp_ns);
+        self.pending.push(Reverse(Pending {
+            timestamp_ns,
+            arrival: self.arrivals,
+            event,
+        }));
+        self.arrivals += 1;
+        outcome
+    }
+
+    /// Treat `timestamp_ns`, a `CLOCK_MONOTONIC` time, as seen, so that
+    /// events are released while no new events arrive.
+    pub fn advance_to(&mut self, timestamp_ns: u64) {
+        self.newest_ns = self.newest_ns.max(timestamp_ns);
+    }
+
+    /// Release the oldest event if it is older than the window.
+    pub fn pop(&mut self) -> Option<ProcEvent> {
+        let watermark = self.newest_ns.saturating_sub(self.window_ns);
+        match self.pending.peek() {
+            Some(Reverse(pending)) if pending.timestamp_ns <= watermark => {
+                self.pop_pending()
+            }
+            _ => None,
+        }
+    }
+
+    /// Release every event that is older than the window, oldest first.
+    pub fn drain_ready(&mut self) -> impl Iterator<Item = ProcEvent> + '_ {
+        std::iter::from_fn(move || self.pop())
+    }
+
+    /// Release every buffered event, oldest first, for instance on
+    /// shutdown.
+    pub fn flush(&mut self) -> impl Iterator<Item = ProcEvent> + '_ {
+        std::iter::from_fn(move || self.pop_pending())
+    }
+
+    fn pop_pending(&mut self) -> Option<ProcEvent> {
+        let Reverse(pending) = self.pending.pop()?;
+        self.released_ns =
+            Some(self.released_ns.map_or(pending.timestamp_ns, |ns| {
+                ns.max(pending.timestamp_ns)
+            }));
+        Some(pending.event)
+    }
+
+    /// The number of events held back.
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    pub fn stats(&self) -> ReorderStats {
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exec(timestamp_ns: u64, pid: i32) -> ProcEvent {
+        ProcEvent::Exec {
+            cpu: 0,
+            timestamp_ns,
+            process_pid: pid,
+            process_tgid: pid,
+        }
+    }
+
+    fn pids(events: impl Iterator<Item = ProcEvent>) -> Vec<i32> {
+        events
+            .map(|event| match event {
+                ProcEvent::Exec { process_pid, .. } => process_pid,
+                event => panic!("unexpected {event:?}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn outcomes() {
+        let mut reorder = ProcEventReorder::new(Duration::from_nanos(10));
+        assert_eq!(reorder.push(exec(100, 1)), ReorderOutcome::InOrder);
+        assert_eq!(reorder.push(exec(120, 2)), ReorderOutcome::InOrder);
+        assert_eq!(reorder.push(exec(110, 3)), ReorderOutcome::Reordered);
+
+        // only events at least the window older than the newest go.
+        assert_eq!(pids(reorder.drain_ready()), [1, 3]);
+        assert_eq!(reorder.len(), 1);
+
+        assert_eq!(reorder.push(exec(105, 4)), ReorderOutcome::Late);
+        assert_eq!(
+            reorder.stats(),
+            ReorderStats {
+                in_order: 2,
+                reordered: 1,
+                late: 1,
+            },
+        );
+    }
+
+    #[test]
+    fn equal_timestamps_keep_arrival_order() {
+        let mut reorder = ProcEventReorder::new(Duration::from_nanos(10));
+        for pid in 1..=3 {
+            reorder.push(exec(100, pid));
+        }
+        reorder.push(exec(90, 4));
+        reorder.push(exec(100, 5));
+        assert_eq!(pids(reorder.flush()), [4, 1, 2, 3, 5]);
+    }
+
+    #[test]
+    fn advance_to_releases_held_events() {
+        let mut reorder = ProcEventReorder::new(Duration::from_nanos(10));
+        reorder.push(exec(100, 1));
+        reorder.push(exec(105, 2));
+        assert_eq!(reorder.pop(), None);
+
+        reorder.advance_to(110);
+        assert_eq!(pids(reorder.drain_ready()), [1]);
+        reorder.advance_to(115);
+        assert_eq!(pids(reorder.drain_ready()), [2]);
+        assert!(reorder.is_empty());
+    }
+
+    #[test]
+    fn push_after_flush_is_late() {
+        let mut reorder = ProcEventReorder::new(Duration::from_secs(1));
+        reorder.push(exec(100, 1));
+        reorder.push(exec(120, 2));
+        assert_eq!(pids(reorder.flush()), [1, 2]);
+        assert!(reorder.is_empty());
+
+        assert_eq!(reorder.push(exec(110, 3)), ReorderOutcome::Late);
+        assert_eq!(reorder.push(exec(120, 4)), ReorderOutcome::InOrder);
+        assert_eq!(pids(reorder.flush()), [3, 4]);
+    }
+}
@@ -0,0 +1,248 @@
+use std::{any::Any, collections::HashMap, fmt};
+
+use super::{
+    proc::ProcEvent, ConnectorId, ConnectorMessage, ConnectorMessageRef,
+    ConnectorPayload, DeserializeError,
+};
+
+/// The payload of a connector message decoded by a [`DecoderRegistry`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum DecodedMessage {
+    /// An event from the proc connector, [`ConnectorId::PROC`].
+    Proc(ProcEvent),
+    /// A payload produced by a decoder registered by the user.
+    Custom {
+        id: ConnectorId,
+        payload: Box<dyn Any + Send + Sync>,
+    },
+    /// No decoder is registered for the message's id, the data is kept
+    /// as is.
+    Unknown { id: ConnectorId, data: Vec<u8> },
+}
+
+impl DecodedMessage {
+    pub fn id(&self) -> ConnectorId {
+        match self {
+            DecodedMessage::Proc(_) => ConnectorId::PROC,
+            DecodedMessage::Custom { id, .. }
+            | DecodedMessage::Unknown { id, .. } => *id,
+        }
+    }
+
+    /// The payload of a [`DecodedMessage::Custom`] message, if it is a
+    /// `P`.
+    pub fn downcast_ref<P: Any>(&self) -> Option<&P> {
+        match self {
+            DecodedMessage::Custom { payload, .. } => payload.downcast_ref(),
+            _ => None,
+        }
+    }
+}
+
+type DecodeResult = Result<DecodedMessage, DeserializeError>;
+
+type Decoder =
+    Box<dyn Fn(&ConnectorMessageRef<'_>) -> DecodeResult + Send + Sync>;
+
+/// Decodes messages from several connectors through one entry point,
+/// picking the decoder registered for each message's [`ConnectorId`].
+///
+/// Messages for ids without a decoder are returned as
+/// [`DecodedMessage::Unknown`], so nothing is lost.
+pub struct DecoderRegistry {
+    decoders: HashMap<ConnectorId, Decoder>,
+}
+
+impl DecoderRegistry {
+    /// Create a registry with decoders for the connector families this
+    /// crate supports, currently [`ConnectorId::PROC`].
+    pub fn new() -> Self {
+        let mut registry = Self::empty();
+        registry.register(ConnectorId::PROC, |message| {
+            Ok(DecodedMessage::Proc(
+                message.decode::<ProcEvent>()?.into_payload(),
+            ))
+        });
+        registry
+    }
+
+    /// Create a registry without any decoders.
+    pub fn empty() -> Self {
+        DecoderRegistry {
+            decoders: HashMap::new(),
+        }
+    }
+
+    /// Register `decoder` for messages sent to `id`, replacing any
+    /// decoder registered for it before.
+    pub fn register<F>(&mut self, id: ConnectorId, decoder: F) -> &mut Self
+    where
+        F: Fn(&ConnectorMessageRef<'_>) -> DecodeResult + Send + Sync + 'static,
+    {
+        self.decoders.insert(id, Box::new(decoder));
+        self
+    }
+
+    /// Register a decoder producing [`DecodedMessage::Custom`] messages
+    /// holding a `P`, for the id given by [`ConnectorPayload::ID`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `P` is not tied to a connector id.
+    pub fn register_payload<P>(&mut self) -> &mut Self
+    where
+        P: ConnectorPayload + Send + Sync + 'static,
+    {
+        let id = P::ID.expect("payload type has no connector id");
+        self.register(id, move |message| {
+            Ok(DecodedMessage::Custom {
+                id,
+                payload: Box::new(message.decode::<P>()?.into_payload()),
+            })
+        })
+    }
+
+    /// Remove the decoder for `id`, returning whether there was one.
+    pub fn unregister(&mut self, id: ConnectorId) -> bool {
+        self.decoders.remove(&id).is_some()
+    }
+
+    pub fn contains(&self, id: ConnectorId) -> bool {
+        self.decoders.contains_key(&id)
+    }
+
+    /// Decode the payload of `message` with the decoder for its id.
+    pub fn decode(
+        &self,
+        message: &ConnectorMessage,
+    ) -> Result<DecodedMessage, DeserializeError> {
+        self.decode_ref(&message.as_borrowed())
+    }
+
+    /// Like [`Self::decode`], for a borrowed message.
+    pub fn decode_ref(
+        &self,
+        message: &ConnectorMessageRef<'_>,
+    ) -> Result<DecodedMessage, DeserializeError> {
+        match self.decoders.get(&message.id()) {
+            Some(decoder) => decoder(message),
+            None => Ok(DecodedMessage::Unknown {
+                id: message.id(),
+                data: message.data().to_vec(),
+            }),
+        }
+    }
+}
+
+impl Default for DecoderRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for DecoderRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut ids: Vec<_> = self.decoders.keys().collect();
+        ids.sort();
+        f.debug_struct("DecoderRegistry")
+            .field("ids", &ids)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::Endianness;
+
+    // A payload of the W1 connector holding one u32.
+    #[derive(Debug, PartialEq)]
+    struct Counter(u32);
+
+    impl ConnectorPayload for Counter {
+        const ID: Option<ConnectorId> = Some(ConnectorId::W1);
+
+        fn payload_len(&self) -> usize {
+            4
+        }
+
+        fn emit_payload(&self, buffer: &mut [u8], endianness: Endianness) {
+            endianness.write_u32(buffer, self.0)
+        }
+
+        fn parse_payload(
+            data: &[u8],
+            endianness: Endianness,
+        ) -> Result<Self, DeserializeError> {
+            match data.len() {
+                4 => Ok(Counter(endianness.read_u32(data))),
+                len => Err(DeserializeError::InvalidLength { len }),
+            }
+        }
+    }
+
+    fn counter_message(count: u32) -> ConnectorMessage {
+        ConnectorMessage::from_payload(Counter(count)).to_raw()
+    }
+
+    #[test]
+    fn unknown_ids_keep_their_data() {
+        let registry = DecoderRegistry::new();
+        let message = ConnectorMessage::new(42, 7, 0, 0, 0, vec![1, 2, 3]);
+        match registry.decode(&message).unwrap() {
+            DecodedMessage::Unknown { id, data } => {
+                assert_eq!(id, ConnectorId::new(42, 7));
+                assert_eq!(data, [1, 2, 3]);
+            }
+            decoded => panic!("unexpected {decoded:?}"),
+        }
+    }
+
+    #[test]
+    fn empty_registry_decodes_nothing() {
+        let registry = DecoderRegistry::empty();
+        assert!(!registry.contains(ConnectorId::PROC));
+        let message = ConnectorMessage::builder()
+            .id(ConnectorId::PROC)
+            .data([0; 4])
+            .build();
+        let decoded = registry.decode(&message).unwrap();
+        assert!(matches!(decoded, DecodedMessage::Unknown { .. }));
+        assert_eq!(decoded.id(), ConnectorId::PROC);
+    }
+
+    #[test]
+    fn register_payload() {
+        let mut registry = DecoderRegistry::new();
+        registry.register_payload::<Counter>();
+        assert!(registry.contains(ConnectorId::W1));
+
+        let decoded = registry.decode(&counter_message(5)).unwrap();
+        assert_eq!(decoded.id(), ConnectorId::W1);
+        assert_eq!(decoded.downcast_ref::<Counter>(), Some(&Counter(5)));
+        assert_eq!(decoded.downcast_ref::<u32>(), None);
+
+        let message = ConnectorMessage::builder()
+            .id(ConnectorId::W1)
+            .data([0; 3])
+            .build();
+        assert_eq!(
+            registry.decode(&message).unwrap_err(),
+            DeserializeError::InvalidLength { len: 3 },
+        );
+    }
+
+    #[test]
+    fn unregister() {
+        let mut registry = DecoderRegistry::new();
+        registry.register_payload::<Counter>();
+        assert!(registry.unregister(ConnectorId::W1));
+        assert!(!registry.unregister(ConnectorId::W1));
+        assert!(!registry.contains(ConnectorId::W1));
+
+        let decoded = registry.decode(&counter_message(5)).unwrap();
+        assert!(matches!(decoded, DecodedMessage::Unknown { .. }));
+        assert_eq!(decoded.downcast_ref::<Counter>(), None);
+    }
+}
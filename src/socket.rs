@@ -0,0 +1,652 @@
+//! A blocking `NETLINK_CONNECTOR` socket, and with the `tokio` or
+//! `smol` feature an asynchronous one. With the `mio` feature the
+//! socket can be registered with a `mio::Poll`.
+
+#[cfg(feature = "mio")]
+mod mio;
+#[cfg(feature = "smol")]
+mod smol;
+#[cfg(feature = "tokio")]
+mod tokio;
+
+use std::{
+    collections::BTreeSet,
+    error::Error,
+    fmt, io,
+    os::fd::{AsFd, AsRawFd, BorrowedFd, RawFd},
+};
+
+use netlink_packet_core::{
+    NetlinkBuffer, NetlinkMessage, NetlinkPayload, NETLINK_HEADER_LEN,
+    NLMSG_ERROR, NLMSG_NOOP, NLMSG_OVERRUN,
+};
+use netlink_sys::{constants::NETLINK_CONNECTOR, Socket, SocketAddr};
+
+use crate::protocol::{
+    nlmsg_align, ConnectorId, ConnectorMessage, ConnectorMessageRefs,
+    ConnectorPayload, DeserializeError, SerializeError, CONNECTOR_MAX_MSG_SIZE,
+};
+
+#[cfg(feature = "smol")]
+pub use self::smol::SmolConnectorSocket;
+#[cfg(feature = "tokio")]
+pub use self::tokio::TokioConnectorSocket;
+
+/// Size of the buffer datagrams are received into, enough for the
+/// largest message the connector sends.
+pub const RECV_BUFFER_LEN: usize = NETLINK_HEADER_LEN + CONNECTOR_MAX_MSG_SIZE;
+
+/// A socket of the `NETLINK_CONNECTOR` family, sending and receiving
+/// [`ConnectorMessage`]s.
+///
+/// Messages from the kernel are multicast to the group numbered by the
+/// [`ConnectorId::idx`] of their connector, so the socket has to
+/// [`join`](Self::join) that group, or [`bind`](Self::bind) to it, to
+/// receive them. Older kernels only let processes with
+/// `CAP_NET_ADMIN` join, failing with [`SocketError::Join`] otherwise.
+#[derive(Debug)]
+pub struct ConnectorSocket {
+    socket: Socket,
+    buffer: Vec<u8>,
+    memberships: BTreeSet<ConnectorId>,
+}
+
+impl ConnectorSocket {
+    /// Open an unbound socket.
+    pub fn new() -> Result<Self, SocketError> {
+        Ok(ConnectorSocket {
+            socket: Socket::new(NETLINK_CONNECTOR)?,
+            buffer: vec![0; RECV_BUFFER_LEN],
+            memberships: BTreeSet::new(),
+        })
+    }
+
+    /// Bind the socket, joining the multicast groups in the `groups`
+    /// mask, see [`ConnectorId::group_mask`], and leaving the other
+    /// groups up to 32.
+    ///
+    /// The mask selects groups, not connector ids, so the groups joined
+    /// this way are not reported by [`Self::memberships`] and cannot be
+    /// left with [`Self::leave`]; bind again to leave them.
+    pub fn bind(&mut self, groups: u32) -> Result<(), SocketError> {
+        // keep the port the socket already has, the kernel refuses to
+        // change it once assigned.
+        let mut addr = SocketAddr::new(0, 0);
+        self.socket.get_address(&mut addr)?;
+        self.socket
+            .bind(&SocketAddr::new(addr.port_number(), groups))?;
+        self.memberships
+            .retain(|id| id.group_mask().is_none_or(|bit| groups & bit != 0));
+        Ok(())
+    }
+
+    /// Join the multicast group of connector `id` to receive its
+    /// messages, with `NETLINK_ADD_MEMBERSHIP`.
+    ///
+    /// Joining an id whose group is already joined only records the id.
+    /// Groups the kernel refuses, such as those past the connector's
+    /// last one, fail with [`SocketError::Join`].
+    pub fn join(&mut self, id: ConnectorId) -> Result<(), SocketError> {
+        if !self.is_group_member(id.idx) {
+            self.socket
+                .add_membership(id.idx)
+                .map_err(|error| SocketError::Join { id, error })?;
+        }
+        self.memberships.insert(id);
+        Ok(())
+    }
+
+    /// Stop receiving the messages of connector `id` joined with
+    /// [`Self::join`]. The group is only left once no other joined id
+    /// shares it. Ids never joined, including those whose group was
+    /// joined through [`Self::bind`], are ignored.
+    pub fn leave(&mut self, id: ConnectorId) -> Result<(), SocketError> {
+        if !self.memberships.remove(&id) || self.is_group_member(id.idx) {
+            return Ok(());
+        }
+        self.socket.drop_membership(id.idx).map_err(|error| {
+            self.memberships.insert(id);
+            SocketError::Leave { id, error }
+        })
+    }
+
+    /// The ids joined with [`Self::join`] and not left since.
+    pub fn memberships(&self) -> impl Iterator<Item = ConnectorId> + '_ {
+        self.memberships.iter().copied()
+    }
+
+    /// Whether `id` was joined with [`Self::join`].
+    pub fn is_member(&self, id: ConnectorId) -> bool {
+        self.memberships.contains(&id)
+    }
+
+    fn is_group_member(&self, idx: u32) -> bool {
+        self.memberships.iter().any(|id| id.idx == idx)
+    }
+
+    /// Set the kernel's receive buffer size, `SO_RCVBUF`, in bytes.
+    ///
+    /// Events arriving while the buffer is full are dropped, and the next
+    /// receive fails with `ENOBUFS`, see [`SocketError::is_overflow`]. The
+    /// kernel doubles the value and caps it at
+    /// `/proc/sys/net/core/rmem_max`.
+    pub fn set_receive_buffer_size(
+        &self,
+        size: usize,
+    ) -> Result<(), SocketError> {
+        let size = libc::c_int::try_from(size).unwrap_or(libc::c_int::MAX);
+        self.socket.set_rx_buf_sz(size)?;
+        Ok(())
+    }
+
+    /// The kernel's receive buffer size, `SO_RCVBUF`, in bytes.
+    pub fn receive_buffer_size(&self) -> Result<usize, SocketError> {
+        Ok(self.socket.get_rx_buf_sz()?)
+    }
+
+    /// Switch the socket between blocking and non-blocking mode. In
+    /// non-blocking mode, [`Self::recv`] fails with an
+    /// [`io::ErrorKind::WouldBlock`] error when no datagram is queued.
+    pub fn set_non_blocking(
+        &self,
+        non_blocking: bool,
+    ) -> Result<(), SocketError> {
+        self.socket.set_non_blocking(non_blocking)?;
+        Ok(())
+    }
+
+    /// Send `message` to the kernel, using its `seq` as the netlink
+    /// sequence number.
+    pub fn send<P: ConnectorPayload>(
+        &self,
+        message: ConnectorMessage<P>,
+    ) -> Result<(), SocketError> {
+        self.send_bytes(&encode_message(message)?)?;
+        Ok(())
+    }
+
+    /// Send a complete netlink message to the kernel, such as one built
+    /// by [`ProcControl`](crate::protocol::proc::ProcControl).
+    ///
+    /// The message must have been
+    /// [`finalize`](NetlinkMessage::finalize)d, and its connector
+    /// message must pass [`ConnectorMessage::validate`].
+    pub fn send_netlink<P: ConnectorPayload>(
+        &self,
+        message: &NetlinkMessage<ConnectorMessage<P>>,
+    ) -> Result<(), SocketError> {
+        self.send_bytes(&encode_netlink(message)?)?;
+        Ok(())
+    }
+
+    pub(crate) fn send_bytes(&self, buffer: &[u8]) -> io::Result<()> {
+        self.socket.send_to(buffer, &SocketAddr::new(0, 0), 0)?;
+        Ok(())
+    }
+
+    /// Block until a datagram arrives and return the messages in it.
+    ///
+    /// A datagram with a malformed record fails as a whole, losing the
+    /// messages before that record; [`Self::recv_into`] keeps them.
+    pub fn recv(&mut self) -> Result<Vec<ConnectorMessage>, SocketError> {
+        let mut messages = Vec::new();
+        self.recv_into(&mut messages)?;
+        Ok(messages)
+    }
+
+    /// Block until a datagram arrives, appending its messages to
+    /// `messages` and returning how many were added.
+    ///
+    /// If a record in the datagram is malformed, the messages before it
+    /// are still appended and the rest of the datagram is dropped.
+    pub fn recv_into(
+        &mut self,
+        messages: &mut Vec<ConnectorMessage>,
+    ) -> Result<usize, SocketError> {
+        let start = messages.len();
+        recv_datagram(&self.socket, &mut self.buffer, messages, 0)?;
+        Ok(messages.len() - start)
+    }
+
+    /// Receive every datagram already queued without blocking, appending
+    /// their messages to `messages` and returning how many were added.
+    ///
+    /// This does not depend on the socket being in non-blocking mode.
+    /// Edge-triggered event loops such as mio only report the socket as
+    /// readable again after it was drained, which this does. On error,
+    /// the messages received before it, including those of a datagram
+    /// with a malformed record, are still appended.
+    pub fn try_recv(
+        &mut self,
+        messages: &mut Vec<ConnectorMessage>,
+    ) -> Result<usize, SocketError> {
+        let start = messages.len();
+        loop {
+            let received = recv_datagram(
+                &self.socket,
+                &mut self.buffer,
+                messages,
+                libc::MSG_DONTWAIT,
+            );
+            match received {
+                Ok(()) => {}
+                Err(SocketError::Io(e))
+                    if e.kind() == io::ErrorKind::WouldBlock =>
+                {
+                    return Ok(messages.len() - start);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// The underlying netlink socket.
+    pub fn as_socket(&self) -> &Socket {
+        &self.socket
+    }
+}
+
+impl AsRawFd for ConnectorSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.socket.as_raw_fd()
+    }
+}
+
+impl AsFd for ConnectorSocket {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.socket.as_fd()
+    }
+}
+
+// Serialize `message` in a netlink message, using its `seq` as the
+// netlink sequence number.
+pub(crate) fn encode_message<P: ConnectorPayload>(
+    message: ConnectorMessage<P>,
+) -> Result<Vec<u8>, SocketError> {
+    let seq = message.seq();
+    let mut message = NetlinkMessage::from(message);
+    message.header.sequence_number = seq;
+    message.finalize();
+    encode_netlink(&message)
+}
+
+// Serialize `message`, checking it first since `serialize` panics on
+// invalid connector messages.
+pub(crate) fn encode_netlink<P: ConnectorPayload>(
+    message: &NetlinkMessage<ConnectorMessage<P>>,
+) -> Result<Vec<u8>, SocketError> {
+    if let NetlinkPayload::InnerMessage(message) = &message.payload {
+        message.validate()?;
+    }
+    if message.header.length as usize != message.buffer_len()
+        || message.header.message_type != message.payload.message_type()
+    {
+        return Err(SerializeError::NotFinalized.into());
+    }
+    let mut buffer = vec![0; message.buffer_len()];
+    message.serialize(&mut buffer);
+    Ok(buffer)
+}
+
+// Receive one datagram into `buffer` with the extra `recv` `flags` and
+// parse it into `messages`.
+pub(crate) fn recv_datagram(
+    socket: &Socket,
+    buffer: &mut [u8],
+    messages: &mut Vec<ConnectorMessage>,
+    flags: libc::c_int,
+) -> Result<(), SocketError> {
+    let capacity = buffer.len();
+    let size = socket.recv(&mut &mut buffer[..], libc::MSG_TRUNC | flags)?;
+    if size > capacity {
+        return Err(SocketError::Truncated { size, capacity });
+    }
+    parse_datagram(&buffer[..size], messages)
+}
+
+// Split a received datagram into its netlink messages and those into
+// their connector messages. The payloads are parsed directly rather
+// than through `NetlinkMessage`, which would take the `NLMSG_DONE`
+// messages the kernel sends for the end of a dump. The messages are
+// appended to `messages` as they are parsed, so those before an error
+// are kept.
+pub(crate) fn parse_datagram(
+    mut data: &[u8],
+    messages: &mut Vec<ConnectorMessage>,
+) -> Result<(), SocketError> {
+    while !data.is_empty() {
+        if data.len() < NETLINK_HEADER_LEN {
+            return Err(DeserializeError::Truncated {
+                needed: NETLINK_HEADER_LEN,
+                got: data.len(),
+            }
+            .into());
+        }
+        let buf = NetlinkBuffer::new(data);
+        let len = buf.length() as usize;
+        if len < NETLINK_HEADER_LEN || len > data.len() {
+            return Err(DeserializeError::Truncated {
+                needed: len.max(NETLINK_HEADER_LEN),
+                got: data.len(),
+            }
+            .into());
+        }
+        let payload = &data[NETLINK_HEADER_LEN..len];
+
+        match buf.message_type() {
+            NLMSG_NOOP => {}
+            NLMSG_ERROR => {
+                if payload.len() < 4 {
+                    return Err(DeserializeError::Truncated {
+                        needed: 4,
+                        got: payload.len(),
+                    }
+                    .into());
+                }
+                let code = i32::from_ne_bytes(payload[..4].try_into().unwrap());
+                if code != 0 {
+                    let errno = code.wrapping_neg();
+                    return Err(io::Error::from_raw_os_error(errno).into());
+                }
+            }
+            message_type @ NLMSG_OVERRUN => {
+                return Err(DeserializeError::UnexpectedMessageType {
+                    message_type,
+                }
+                .into());
+            }
+            message_type => {
+                for message in ConnectorMessageRefs::new(payload) {
+                    messages.push(
+                        message?.with_message_type(message_type).to_owned(),
+                    );
+                }
+            }
+        }
+
+        data = &data[nlmsg_align(len).min(data.len())..];
+    }
+    Ok(())
+}
+
+/// Error returned by [`ConnectorSocket`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum SocketError {
+    /// A system call failed, or the kernel answered with an error.
+    Io(io::Error),
+    /// A message to send is invalid.
+    Serialize(SerializeError),
+    /// A received datagram is malformed.
+    Deserialize(DeserializeError),
+    /// A datagram of `size` bytes did not fit the receive buffer and was
+    /// cut off.
+    Truncated { size: usize, capacity: usize },
+    /// The multicast group of connector `id` could not be joined.
+    Join { id: ConnectorId, error: io::Error },
+    /// The multicast group of connector `id` could not be left.
+    Leave { id: ConnectorId, error: io::Error },
+}
+
+impl SocketError {
+    /// Whether the socket's receive buffer overflowed and messages were
+    /// lost, `ENOBUFS`.
+    pub fn is_overflow(&self) -> bool {
+        matches!(
+            self,
+            SocketError::Io(e) if e.raw_os_error() == Some(libc::ENOBUFS)
+        )
+    }
+}
+
+impl Error for SocketError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SocketError::Io(e) => Some(e),
+            SocketError::Serialize(e) => Some(e),
+            SocketError::Deserialize(e) => Some(e),
+            SocketError::Truncated { .. } => None,
+            SocketError::Join { error, .. }
+            | SocketError::Leave { error, .. } => Some(error),
+        }
+    }
+}
+
+impl fmt::Display for SocketError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SocketError::Io(_) => write!(f, "connector socket I/O failed"),
+            SocketError::Serialize(_) => {
+                write!(f, "cannot serialize connector message")
+            }
+            SocketError::Deserialize(_) => {
+                write!(f, "cannot parse received connector message")
+            }
+            SocketError::Truncated { size, capacity } => write!(
+                f,
+                "datagram of {size} bytes exceeds the {capacity} byte \
+                 receive buffer"
+            ),
+            SocketError::Join { id, error } => match error.raw_os_error() {
+                Some(libc::EPERM) => write!(
+                    f,
+                    "joining the multicast group of connector {id} needs \
+                     CAP_NET_ADMIN"
+                ),
+                Some(libc::ENOENT | libc::EINVAL) => write!(
+                    f,
+                    "connector {id} has no multicast group {}",
+                    id.idx
+                ),
+                _ => write!(
+                    f,
+                    "cannot join the multicast group of connector {id}"
+                ),
+            },
+            SocketError::Leave { id, .. } => {
+                write!(f, "cannot leave the multicast group of connector {id}")
+            }
+        }
+    }
+}
+
+impl From<io::Error> for SocketError {
+    fn from(e: io::Error) -> Self {
+        SocketError::Io(e)
+    }
+}
+
+impl From<SerializeError> for SocketError {
+    fn from(e: SerializeError) -> Self {
+        SocketError::Serialize(e)
+    }
+}
+
+impl From<DeserializeError> for SocketError {
+    fn from(e: DeserializeError) -> Self {
+        SocketError::Deserialize(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Ids 3:1 and 3:2 share multicast group 3, 4:1 has its own.
+    const SHARED_A: ConnectorId = ConnectorId::new(3, 1);
+    const SHARED_B: ConnectorId = ConnectorId::new(3, 2);
+    const OTHER: ConnectorId = ConnectorId::new(4, 1);
+
+    // A socket that joined `ids`, or `None` where joining needs
+    // privileges the test lacks.
+    fn joined(ids: &[ConnectorId]) -> Option<ConnectorSocket> {
+        let mut socket = ConnectorSocket::new().unwrap();
+        for id in ids {
+            match socket.join(*id) {
+                Ok(()) => {}
+                Err(SocketError::Join { error, .. })
+                    if error.raw_os_error() == Some(libc::EPERM) =>
+                {
+                    return None;
+                }
+                Err(e) => panic!("cannot join {id}: {e}"),
+            }
+        }
+        Some(socket)
+    }
+
+    #[test]
+    fn join_ids_sharing_a_group() {
+        let Some(mut socket) = joined(&[SHARED_A, SHARED_B, OTHER]) else {
+            return;
+        };
+        assert_eq!(
+            socket.memberships().collect::<Vec<_>>(),
+            [SHARED_A, SHARED_B, OTHER],
+        );
+        // joining again only records the id once.
+        socket.join(SHARED_A).unwrap();
+        assert_eq!(socket.memberships().count(), 3);
+
+        socket.leave(SHARED_A).unwrap();
+        assert!(!socket.is_member(SHARED_A));
+        assert!(socket.is_member(SHARED_B));
+        assert!(socket.is_group_member(3));
+
+        socket.leave(SHARED_B).unwrap();
+        assert!(!socket.is_group_member(3));
+        // ids not joined are ignored.
+        socket.leave(SHARED_B).unwrap();
+        assert_eq!(socket.memberships().collect::<Vec<_>>(), [OTHER]);
+    }
+
+    #[test]
+    fn bind_prunes_memberships() {
+        let Some(mut socket) = joined(&[SHARED_A, OTHER]) else {
+            return;
+        };
+        let high = ConnectorId::new(40, 1);
+        socket.memberships.insert(high);
+        socket.bind(OTHER.group_mask().unwrap()).unwrap();
+        // groups above 32 are not affected by the bind mask.
+        assert_eq!(socket.memberships().collect::<Vec<_>>(), [OTHER, high]);
+    }
+
+    #[test]
+    fn join_error_names_the_connector() {
+        let error = |errno| SocketError::Join {
+            id: ConnectorId::PROC,
+            error: io::Error::from_raw_os_error(errno),
+        };
+        assert_eq!(
+            error(libc::EPERM).to_string(),
+            "joining the multicast group of connector CN_IDX_PROC needs \
+             CAP_NET_ADMIN",
+        );
+        for errno in [libc::ENOENT, libc::EINVAL] {
+            assert_eq!(
+                error(errno).to_string(),
+                "connector CN_IDX_PROC has no multicast group 1",
+            );
+        }
+        assert_eq!(
+            error(libc::ENOMEM).to_string(),
+            "cannot join the multicast group of connector CN_IDX_PROC",
+        );
+        let leave = SocketError::Leave {
+            id: ConnectorId::new(42, 7),
+            error: io::Error::from_raw_os_error(libc::EINVAL),
+        };
+        assert_eq!(
+            leave.to_string(),
+            "cannot leave the multicast group of connector 42:7",
+        );
+    }
+
+    #[test]
+    fn is_overflow() {
+        let overflow = io::Error::from_raw_os_error(libc::ENOBUFS);
+        assert!(SocketError::Io(overflow).is_overflow());
+        let other = io::Error::from_raw_os_error(libc::EINTR);
+        assert!(!SocketError::Io(other).is_overflow());
+        let join = SocketError::Join {
+            id: ConnectorId::PROC,
+            error: io::Error::from_raw_os_error(libc::ENOBUFS),
+        };
+        assert!(!join.is_overflow());
+    }
+
+    #[test]
+    fn parse_keeps_messages_before_malformed_record() {
+        let message = ConnectorMessage::new(1, 1, 7, 8, 0, vec![1, 2, 3, 4]);
+        let mut data = encode_message(message.clone()).unwrap();
+        // A second record whose length runs past the end of the payload.
+        let mut bad = [0; 20];
+        bad[16..18].copy_from_slice(&u16::MAX.to_ne_bytes());
+        data.extend_from_slice(&bad);
+        let len = data.len() as u32;
+        data[..4].copy_from_slice(&len.to_ne_bytes());
+
+        let mut messages = Vec::new();
+        assert!(parse_datagram(&data, &mut messages).is_err());
+        assert_eq!(messages, [message]);
+    }
+
+    #[test]
+    fn parse_error_codes() {
+        let datagram = |code: i32| {
+            let mut data = vec![0; NETLINK_HEADER_LEN + 4];
+            let len = data.len() as u32;
+            data[..4].copy_from_slice(&len.to_ne_bytes());
+            data[4..6].copy_from_slice(&NLMSG_ERROR.to_ne_bytes());
+            data[NETLINK_HEADER_LEN..].copy_from_slice(&code.to_ne_bytes());
+            data
+        };
+        let errno = |code| match parse_datagram(&datagram(code), &mut vec![]) {
+            Err(SocketError::Io(e)) => e.raw_os_error(),
+            other => panic!("unexpected result {other:?}"),
+        };
+
+        // An acknowledgement.
+        assert!(parse_datagram(&datagram(0), &mut vec![]).is_ok());
+        assert_eq!(errno(-libc::EPERM), Some(libc::EPERM));
+        // Codes the kernel never sends must not overflow.
+        assert_eq!(errno(i32::MIN), Some(i32::MIN));
+    }
+
+    // The socket is left in blocking mode, `try_recv` must not wait.
+    #[test]
+    fn try_recv_empty_queue() {
+        let mut socket = ConnectorSocket::new().unwrap();
+        let mut messages = Vec::new();
+        assert_eq!(socket.try_recv(&mut messages).unwrap(), 0);
+        assert!(messages.is_empty());
+    }
+
+    #[test]
+    fn encode_netlink_rejects_invalid_messages() {
+        let message = ConnectorMessage::new(1, 1, 0, 0, 0, vec![0; 20000]);
+        let mut message = NetlinkMessage::from(message);
+        message.finalize();
+        assert!(matches!(
+            encode_netlink(&message),
+            Err(SocketError::Serialize(SerializeError::MessageTooLarge {
+                size: 20020,
+                ..
+            })),
+        ));
+
+        let message = NetlinkMessage::from(ConnectorMessage::new(
+            1,
+            1,
+            0,
+            0,
+            0,
+            vec![1, 2, 3, 4],
+        ));
+        assert!(matches!(
+            encode_netlink(&message),
+            Err(SocketError::Serialize(SerializeError::NotFinalized)),
+        ));
+    }
+}
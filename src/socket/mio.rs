@@ -0,0 +1,59 @@
+use std::{io, os::fd::AsRawFd};
+
+use mio::{event::Source, unix::SourceFd, Interest, Registry, Token};
+
+use super::ConnectorSocket;
+
+// Registers the socket's descriptor, so that it can be polled next to
+// other sources. The socket should be switched to non-blocking mode and
+// drained with `try_recv` when readable.
+impl Source for ConnectorSocket {
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        SourceFd(&self.as_raw_fd()).register(registry, token, interests)
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        SourceFd(&self.as_raw_fd()).reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        SourceFd(&self.as_raw_fd()).deregister(registry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use mio::{Events, Poll};
+
+    use super::*;
+
+    #[test]
+    fn register_and_deregister() {
+        let mut socket = ConnectorSocket::new().unwrap();
+        let mut poll = Poll::new().unwrap();
+        poll.registry()
+            .register(&mut socket, Token(0), Interest::READABLE)
+            .unwrap();
+
+        let mut events = Events::with_capacity(4);
+        poll.poll(&mut events, Some(Duration::ZERO)).unwrap();
+        assert!(events.is_empty());
+
+        poll.registry()
+            .reregister(&mut socket, Token(1), Interest::READABLE)
+            .unwrap();
+        poll.registry().deregister(&mut socket).unwrap();
+    }
+}
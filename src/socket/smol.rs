@@ -0,0 +1,155 @@
+use std::{
+    collections::VecDeque,
+    io,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use async_io::Async;
+use futures_core::Stream;
+use netlink_packet_core::NetlinkMessage;
+
+use super::{encode_message, encode_netlink, ConnectorSocket, SocketError};
+use crate::protocol::{ConnectorId, ConnectorMessage, ConnectorPayload};
+
+/// A [`ConnectorSocket`] driven by the `async-io` reactor, receiving as
+/// a [`Stream`] of messages.
+///
+/// `async-io` is the reactor behind smol and async-std, but it works
+/// with any executor, so libraries can use this type without choosing
+/// one for their users. It behaves like the Tokio socket: received
+/// datagrams are buffered until every message in them has been yielded,
+/// dropping a pending `next()` or `send` future loses nothing, and
+/// errors do not end the stream.
+#[derive(Debug)]
+pub struct SmolConnectorSocket {
+    fd: Async<ConnectorSocket>,
+    pending: VecDeque<ConnectorMessage>,
+    // An error to yield once the messages received before it are.
+    error: Option<SocketError>,
+}
+
+impl SmolConnectorSocket {
+    /// Open an unbound socket, see [`ConnectorSocket::new`].
+    pub fn new() -> Result<Self, SocketError> {
+        Self::from_socket(ConnectorSocket::new()?)
+    }
+
+    /// Register `socket` with the reactor, switching it to non-blocking
+    /// mode.
+    pub fn from_socket(socket: ConnectorSocket) -> Result<Self, SocketError> {
+        Ok(SmolConnectorSocket {
+            fd: Async::new(socket)?,
+            pending: VecDeque::new(),
+            error: None,
+        })
+    }
+
+    pub fn socket(&self) -> &ConnectorSocket {
+        self.fd.get_ref()
+    }
+
+    /// Bind the socket to `groups`, see [`ConnectorSocket::bind`].
+    pub fn bind(&mut self, groups: u32) -> Result<(), SocketError> {
+        self.socket_mut().bind(groups)
+    }
+
+    /// Join the multicast group of `id`, see [`ConnectorSocket::join`].
+    pub fn join(&mut self, id: ConnectorId) -> Result<(), SocketError> {
+        self.socket_mut().join(id)
+    }
+
+    /// Leave the multicast group of `id`, see [`ConnectorSocket::leave`].
+    pub fn leave(&mut self, id: ConnectorId) -> Result<(), SocketError> {
+        self.socket_mut().leave(id)
+    }
+
+    fn socket_mut(&mut self) -> &mut ConnectorSocket {
+        // SAFETY: the callers never replace or drop the file descriptor,
+        // they only bind it, change its memberships and receive from it.
+        unsafe { self.fd.get_mut() }
+    }
+
+    /// Send `message` to the kernel, see [`ConnectorSocket::send`].
+    pub async fn send<P: ConnectorPayload>(
+        &self,
+        message: ConnectorMessage<P>,
+    ) -> Result<(), SocketError> {
+        self.send_bytes(&encode_message(message)?).await
+    }
+
+    /// Send a complete netlink message to the kernel, see
+    /// [`ConnectorSocket::send_netlink`]. Messages that cannot be
+    /// serialized fail with [`SocketError::Serialize`] before anything
+    /// is sent.
+    pub async fn send_netlink<P: ConnectorPayload>(
+        &self,
+        message: &NetlinkMessage<ConnectorMessage<P>>,
+    ) -> Result<(), SocketError> {
+        self.send_bytes(&encode_netlink(message)?).await
+    }
+
+    async fn send_bytes(&self, buffer: &[u8]) -> Result<(), SocketError> {
+        self.fd
+            .write_with(|socket| socket.send_bytes(buffer))
+            .await?;
+        Ok(())
+    }
+
+    /// Poll for the next received message.
+    ///
+    /// If a datagram holds a malformed record, the messages before it
+    /// are yielded first, then the error.
+    pub fn poll_recv(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<ConnectorMessage, SocketError>> {
+        loop {
+            if let Some(message) = self.pending.pop_front() {
+                return Poll::Ready(Ok(message));
+            }
+            if let Some(e) = self.error.take() {
+                return Poll::Ready(Err(e));
+            }
+
+            let mut received = Vec::new();
+            let result = self.socket_mut().recv_into(&mut received);
+            self.pending.extend(received);
+            match result {
+                Ok(_) => {}
+                Err(SocketError::Io(e))
+                    if e.kind() == io::ErrorKind::WouldBlock =>
+                {
+                    ready!(self.fd.poll_readable(cx))?;
+                }
+                Err(e) => self.error = Some(e),
+            }
+        }
+    }
+}
+
+impl Stream for SmolConnectorSocket {
+    type Item = Result<ConnectorMessage, SocketError>;
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        self.get_mut().poll_recv(cx).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::task::Waker;
+
+    use super::*;
+
+    #[test]
+    fn empty_queue_is_pending() {
+        let mut socket = SmolConnectorSocket::new().unwrap();
+        let mut cx = Context::from_waker(Waker::noop());
+        assert!(socket.poll_recv(&mut cx).is_pending());
+        assert!(Pin::new(&mut socket).poll_next(&mut cx).is_pending());
+    }
+}
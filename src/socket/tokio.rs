@@ -0,0 +1,174 @@
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use futures_core::Stream;
+use netlink_packet_core::NetlinkMessage;
+use tokio::io::unix::AsyncFd;
+
+use super::{encode_message, encode_netlink, ConnectorSocket, SocketError};
+use crate::protocol::{ConnectorId, ConnectorMessage, ConnectorPayload};
+
+/// A [`ConnectorSocket`] driven by the Tokio reactor, receiving as a
+/// [`Stream`] of messages.
+///
+/// The socket is registered with the reactor of the current runtime;
+/// no threads are spawned. Received datagrams are buffered in the
+/// stream until every message in them has been yielded, so dropping a
+/// pending `next()` or `send` future loses nothing. Errors such as an
+/// overflow, see [`SocketError::is_overflow`], do not end the stream.
+#[derive(Debug)]
+pub struct TokioConnectorSocket {
+    fd: AsyncFd<ConnectorSocket>,
+    pending: VecDeque<ConnectorMessage>,
+    // An error to yield once the messages received before it are.
+    error: Option<SocketError>,
+}
+
+impl TokioConnectorSocket {
+    /// Open an unbound socket, see [`ConnectorSocket::new`].
+    ///
+    /// Must be called within a Tokio runtime.
+    pub fn new() -> Result<Self, SocketError> {
+        Self::from_socket(ConnectorSocket::new()?)
+    }
+
+    /// Register `socket` with the reactor, switching it to non-blocking
+    /// mode.
+    ///
+    /// Must be called within a Tokio runtime.
+    pub fn from_socket(socket: ConnectorSocket) -> Result<Self, SocketError> {
+        socket.set_non_blocking(true)?;
+        Ok(TokioConnectorSocket {
+            fd: AsyncFd::new(socket)?,
+            pending: VecDeque::new(),
+            error: None,
+        })
+    }
+
+    pub fn socket(&self) -> &ConnectorSocket {
+        self.fd.get_ref()
+    }
+
+    /// The wrapped socket, for instance to [`bind`] it.
+    ///
+    /// [`bind`]: ConnectorSocket::bind
+    pub fn socket_mut(&mut self) -> &mut ConnectorSocket {
+        self.fd.get_mut()
+    }
+
+    /// Bind the socket to `groups`, see [`ConnectorSocket::bind`].
+    pub fn bind(&mut self, groups: u32) -> Result<(), SocketError> {
+        self.fd.get_mut().bind(groups)
+    }
+
+    /// Join the multicast group of `id`, see [`ConnectorSocket::join`].
+    pub fn join(&mut self, id: ConnectorId) -> Result<(), SocketError> {
+        self.fd.get_mut().join(id)
+    }
+
+    /// Leave the multicast group of `id`, see [`ConnectorSocket::leave`].
+    pub fn leave(&mut self, id: ConnectorId) -> Result<(), SocketError> {
+        self.fd.get_mut().leave(id)
+    }
+
+    /// Send `message` to the kernel, see [`ConnectorSocket::send`].
+    pub async fn send<P: ConnectorPayload>(
+        &self,
+        message: ConnectorMessage<P>,
+    ) -> Result<(), SocketError> {
+        self.send_bytes(&encode_message(message)?).await
+    }
+
+    /// Send a complete netlink message to the kernel, see
+    /// [`ConnectorSocket::send_netlink`]. Messages that cannot be
+    /// serialized fail with [`SocketError::Serialize`] before anything
+    /// is sent.
+    pub async fn send_netlink<P: ConnectorPayload>(
+        &self,
+        message: &NetlinkMessage<ConnectorMessage<P>>,
+    ) -> Result<(), SocketError> {
+        self.send_bytes(&encode_netlink(message)?).await
+    }
+
+    // A datagram is sent whole or not at all, so the future can be
+    // dropped at any await point.
+    async fn send_bytes(&self, buffer: &[u8]) -> Result<(), SocketError> {
+        loop {
+            let mut guard = self.fd.writable().await?;
+            match guard.try_io(|fd| fd.get_ref().send_bytes(buffer)) {
+                Ok(result) => return Ok(result?),
+                Err(_would_block) => continue,
+            }
+        }
+    }
+
+    /// Poll for the next received message.
+    ///
+    /// If a datagram holds a malformed record, the messages before it
+    /// are yielded first, then the error.
+    pub fn poll_recv(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<ConnectorMessage, SocketError>> {
+        loop {
+            if let Some(message) = self.pending.pop_front() {
+                return Poll::Ready(Ok(message));
+            }
+            if let Some(e) = self.error.take() {
+                return Poll::Ready(Err(e));
+            }
+
+            let mut guard = ready!(self.fd.poll_read_ready_mut(cx))?;
+            let mut received = Vec::new();
+            // Hand I/O errors to `try_io` so that `WouldBlock` clears the
+            // readiness.
+            let result = guard.try_io(|fd| {
+                match fd.get_mut().recv_into(&mut received) {
+                    Err(SocketError::Io(e)) => Err(e),
+                    result => Ok(result),
+                }
+            });
+            self.pending.extend(received);
+            match result {
+                // `Err` is `WouldBlock`, loop to wait for readiness again.
+                Ok(Ok(Ok(_))) | Err(_) => {}
+                Ok(Ok(Err(e))) => self.error = Some(e),
+                Ok(Err(e)) => self.error = Some(e.into()),
+            }
+        }
+    }
+}
+
+impl Stream for TokioConnectorSocket {
+    type Item = Result<ConnectorMessage, SocketError>;
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        self.get_mut().poll_recv(cx).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::task::Waker;
+
+    use super::*;
+
+    #[test]
+    fn empty_queue_is_pending() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_io()
+            .build()
+            .unwrap();
+        let _guard = runtime.enter();
+        let mut socket = TokioConnectorSocket::new().unwrap();
+        let mut cx = Context::from_waker(Waker::noop());
+        assert!(socket.poll_recv(&mut cx).is_pending());
+        assert!(Pin::new(&mut socket).poll_next(&mut cx).is_pending());
+    }
+}